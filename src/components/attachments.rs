@@ -0,0 +1,124 @@
+use iced::{
+    widget::{button, column, container, image, row, text, Row},
+    Alignment, Color, ContentFit, Element, Task,
+};
+use std::path::PathBuf;
+
+use crate::{
+    services,
+    styles::{self, ERROR_RED, WHITE},
+    types::Attachment,
+    utils::context,
+    Message, State,
+};
+
+/// Side of the square image thumbnails.
+const THUMBNAIL_SIZE: f32 = 64.0;
+
+/// Files waiting for the next prompt and the last error.
+pub fn attachment_bar(state: &State) -> Option<Element<'_, Message>> {
+    if state.attachments.is_empty() && state.attach_error.is_none() {
+        return None;
+    }
+    let mut bar = column![chips(&state.attachments, true)].spacing(5);
+    if let Some(error) = &state.attach_error {
+        bar = bar.push(
+            container(text(error).size(13))
+                .padding(5)
+                .style(|_| styles::card(ERROR_RED)),
+        );
+    }
+    Some(bar.into())
+}
+
+/// One chip per attachment, images with their thumbnail, with a button
+/// removing it when `removable`.
+pub fn chips<'l>(attachments: &[Attachment], removable: bool) -> Row<'l, Message> {
+    Row::with_children(attachments.iter().enumerate().map(|(index, attachment)| {
+        let mut chip = row![].spacing(5).align_y(Alignment::Center);
+        if let Some(thumbnail) = &attachment.thumbnail {
+            chip = chip.push(
+                image(thumbnail.clone())
+                    .width(THUMBNAIL_SIZE)
+                    .height(THUMBNAIL_SIZE)
+                    .content_fit(ContentFit::Cover),
+            );
+        }
+        chip = chip.push(
+            text(format!(
+                "{} · {} · ~{} tokens",
+                attachment.name,
+                size_label(attachment.size),
+                context::attachment_tokens(attachment)
+            ))
+            .size(13)
+            .color(Color::BLACK),
+        );
+        if removable {
+            chip = chip.push(
+                button(text("×").size(13))
+                    .on_press(Message::RemoveAttachment(index))
+                    .padding([0, 5])
+                    .style(button::text),
+            );
+        }
+        container(chip)
+            .padding(5)
+            .style(|_| styles::card(WHITE))
+            .into()
+    }))
+    .spacing(5)
+}
+
+fn size_label(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{} KB", bytes / 1024),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Asks for the files to attach in the file chooser of the system.
+pub fn pick_attachments() -> Task<Message> {
+    Task::perform(
+        rfd::AsyncFileDialog::new()
+            .set_title("Attach images, PDFs or text files")
+            .pick_files(),
+        |files| {
+            Message::AttachmentsPicked(
+                files
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|file| file.path().to_path_buf())
+                    .collect(),
+            )
+        },
+    )
+}
+
+pub fn attach_all(paths: Vec<PathBuf>) -> Task<Message> {
+    Task::batch(paths.into_iter().map(attach))
+}
+
+/// Reads a file dropped on the window or picked in the file chooser.
+pub fn attach(path: PathBuf) -> Task<Message> {
+    Task::perform(services::read_attachment(path), Message::Attached)
+}
+
+pub fn handle_attached(state: &mut State, attachment: Result<Attachment, String>) -> Task<Message> {
+    match attachment {
+        Ok(attachment) => {
+            state.attachments.push(attachment);
+            state.attach_error = None;
+        }
+        Err(error) => state.attach_error = Some(error),
+    }
+    Task::none()
+}
+
+pub fn remove_attachment(state: &mut State, index: usize) -> Task<Message> {
+    if index < state.attachments.len() {
+        state.attachments.remove(index);
+    }
+    Task::none()
+}
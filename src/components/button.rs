@@ -1,4 +1,7 @@
-use iced::{widget::button, Element, Theme};
+use iced::{
+    widget::{button, text},
+    Element, Theme,
+};
 
 use crate::Message;
 
@@ -12,3 +15,12 @@ pub fn rounded_button<'a>(
         .style(button_style)
         .into()
 }
+
+/// A small text button sending `copy`, a message copying some text.
+pub fn copy_button<'a>(copy: Message) -> Element<'a, Message> {
+    button(text("Copy").size(11))
+        .on_press(copy)
+        .padding([0, 5])
+        .style(button::text)
+        .into()
+}
@@ -1,6 +1,6 @@
-use iced::{border::Radius, widget::text_input, Color, Element, Task};
+use iced::{widget::text_input, Element, Task};
 
-use crate::{styles::BLUE_SKY, types::FormState, Message};
+use crate::{styles, types::FormState, Message};
 
 pub fn input_form<'l>(
     placeholder: &str,
@@ -10,14 +10,7 @@ pub fn input_form<'l>(
     is_secure: bool,
 ) -> Element<'l, Message> {
     text_input(placeholder, &forms.get(key).cloned().unwrap_or_default())
-        .style(|theme, status| text_input::Style {
-            border: iced::Border {
-                width: 2.0,
-                color: Color::from(BLUE_SKY),
-                radius: Radius::from(8.0),
-            },
-            ..text_input::default(theme, status)
-        })
+        .style(styles::rounded_input)
         .secure(is_secure)
         .on_input(on_input)
         .padding(10)
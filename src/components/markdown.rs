@@ -1,67 +1,251 @@
 use iced::{
-    widget::{column, row, text, Column},
-    Element,
+    alignment::Horizontal,
+    widget::{
+        column, container, mouse_area, rich_text, row, scrollable,
+        scrollable::{Direction, Scrollbar},
+        span, text,
+        text::Wrapping,
+        Column, Row, Space,
+    },
+    Color, Element, Font, Length, Padding,
 };
-use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::sync::{Arc, LazyLock};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Style, Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use super::button::copy_button;
+use crate::{
+    styles::{
+        self, CODE_BACKGROUND, FOOTER_GRAY, INLINE_CODE_BACKGROUND, LINK_BLUE, QUOTE_BACKGROUND,
+        QUOTE_BAR, TABLE_HEADER, WHITE,
+    },
+    types::Message,
+};
+
+const TEXT_SIZE: u16 = 14;
+const LINE_LENGTH: usize = 80;
+/// Cell texts longer than this wrap, keeping tables narrow.
+const CELL_LINE_LENGTH: usize = 40;
+/// Rough width of a character at the body size.
+const CHAR_WIDTH: f32 = 7.5;
+const RULE_WIDTH: f32 = 320.0;
 
-use crate::types::Message;
+/// Loaded with the first code block, parsing the syntaxes takes a moment.
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+/// A light theme, for the light code blocks.
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    ThemeSet::load_defaults()
+        .themes
+        .remove("InspiredGitHub")
+        .unwrap_or_default()
+});
 
-#[derive(Default, Clone, Copy)]
+#[derive(Clone, Debug, Default)]
 struct InlineStyle {
     bold: bool,
     italic: bool,
+    code: bool,
+    strikethrough: bool,
+    /// Target of the link the text is part of, shared by its words.
+    link: Option<Arc<str>>,
 }
 
 impl InlineStyle {
-    fn apply<'a>(&self, content: &'a str, size: u16) -> Element<'a, Message> {
-        let mut txt = text(content).size(size);
-        let mut font = iced::Font::default();
+    fn apply<'a>(&'a self, content: &'a str, size: u16) -> Element<'a, Message> {
+        let mut font = if self.code {
+            Font::MONOSPACE
+        } else {
+            Font::default()
+        };
         if self.bold {
             font.weight = iced::font::Weight::Bold;
-            txt = txt.font(font);
         }
         if self.italic {
             font.style = iced::font::Style::Italic;
-            txt = txt.font(font);
         }
-        txt.into()
+        // Only rich text draws strikethrough and links, which it underlines on hover.
+        let txt: Element<'a, Message> = if let Some(url) = &self.link {
+            mouse_area(rich_text([span(content)
+                .size(size)
+                .font(font)
+                .color(Color::from(LINK_BLUE))
+                .strikethrough(self.strikethrough)
+                .link(Message::OpenLink(url.clone()))]))
+            .on_right_press(Message::Copy(url.clone()))
+            .into()
+        } else if self.strikethrough {
+            rich_text([span(content).size(size).font(font).strikethrough(true)]).into()
+        } else {
+            text(content).size(size).font(font).into()
+        };
+        if self.code {
+            container(txt)
+                .padding([0, 3])
+                .style(|_| styles::card(INLINE_CODE_BACKGROUND))
+                .into()
+        } else {
+            txt
+        }
+    }
+}
+
+/// Splits `code` into consecutive fragments with their color, plain text
+/// for an unknown language.
+fn highlight(language: &str, code: &str) -> Vec<(Color, String)> {
+    let syntax = SYNTAXES
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &THEME);
+    let plain = Style {
+        foreground: THEME
+            .settings
+            .foreground
+            .unwrap_or(syntect::highlighting::Color::BLACK),
+        ..Default::default()
+    };
+    let mut fragments: Vec<(Color, String)> = Vec::new();
+    for line in LinesWithEndings::from(code) {
+        let ranges = highlighter
+            .highlight_line(line, &SYNTAXES)
+            .unwrap_or_else(|_| vec![(plain, line)]);
+        for (style, fragment) in ranges {
+            let color = style.foreground;
+            let color = Color::from_rgba8(color.r, color.g, color.b, f32::from(color.a) / 255.0);
+            match fragments.last_mut() {
+                Some((last, text)) if *last == color => text.push_str(fragment),
+                _ => fragments.push((color, fragment.to_string())),
+            }
+        }
+    }
+    fragments
+}
+
+/// Leaves out the punctuation ending a sentence after an address, and a
+/// closing parenthesis unless the address opened it.
+fn trim_url(mut url: &str) -> &str {
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(rest) if trimmed.matches('(').count() < trimmed.matches(')').count() => rest,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
     }
 }
 
-enum StyledSegment<'a> {
-    Text(&'a str, InlineStyle),
+/// The text of a message parsed once, owning everything the view shows.
+#[derive(Clone, Debug, Default)]
+pub struct Markdown(Vec<Block>);
+
+#[derive(Clone, Debug)]
+struct Word {
+    text: String,
+    style: InlineStyle,
+}
+
+#[derive(Clone, Debug)]
+enum Block {
+    /// Words already wrapped into lines.
+    Text {
+        lines: Vec<Vec<Word>>,
+        size: u16,
+    },
+    Code {
+        language: String,
+        /// Shared with the message its copy button sends.
+        code: Arc<str>,
+        fragments: Vec<(Color, String)>,
+    },
+    Quote(Vec<Block>),
+    /// Items with their number or bullet.
+    List(Vec<(String, Vec<Block>)>),
+    Table {
+        alignments: Vec<Alignment>,
+        /// Characters of the longest line of each column.
+        widths: Vec<usize>,
+        /// Cells of each row, the head first.
+        rows: Vec<Vec<Vec<Block>>>,
+    },
+    Rule,
+}
+
+enum StyledSegment {
+    Text(String, InlineStyle),
     LineBreak,
 }
 
-enum StyledWord<'a> {
-    Text(&'a str, InlineStyle),
-    Break,
+/// A block holding others, built once it ends.
+enum Container {
+    Document,
+    BlockQuote,
+    List {
+        /// The number of the next item of an ordered list.
+        next: Option<u64>,
+        items: Vec<(String, Vec<Block>)>,
+    },
+    Item,
+    Table {
+        alignments: Vec<Alignment>,
+        /// Cells of each row with the characters of their longest line.
+        rows: Vec<Vec<(Vec<Block>, usize)>>,
+    },
+    Cell,
 }
 
-struct MarkdownRenderer<'a> {
-    elements: Vec<Element<'a, Message>>,
-    current_block: Vec<StyledSegment<'a>>,
+struct MarkdownBuilder {
+    /// Open containers with the blocks built in each so far.
+    containers: Vec<(Container, Vec<Block>)>,
+    current_block: Vec<StyledSegment>,
     current_style: InlineStyle,
     current_block_size: u16,
+    /// Language and text of the code block being read.
+    code_block: Option<(String, String)>,
+    /// Text read so far of the current style, the parser splits it at `_`,
+    /// `*`, `&` and such, which addresses can contain.
+    text: String,
 }
 
-impl<'a> MarkdownRenderer<'a> {
+impl MarkdownBuilder {
     fn new() -> Self {
         Self {
-            elements: Vec::new(),
+            containers: vec![(Container::Document, Vec::new())],
             current_block: Vec::new(),
             current_style: InlineStyle::default(),
-            current_block_size: 14,
+            current_block_size: TEXT_SIZE,
+            code_block: None,
+            text: String::new(),
+        }
+    }
+
+    /// Pushes the text read since the last change of style.
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.push_text(&text);
         }
     }
 
-    fn push_text(&mut self, content: &'a str) {
+    fn push_text(&mut self, content: &str) {
         let segments = content.split('\n');
         let mut iter = segments.peekable();
         while let Some(segment) = iter.next() {
-            if !segment.is_empty() {
-                self.current_block
-                    .push(StyledSegment::Text(segment, self.current_style));
+            if self.current_style.link.is_some() || self.current_style.code {
+                if !segment.is_empty() {
+                    self.current_block.push(StyledSegment::Text(
+                        segment.to_string(),
+                        self.current_style.clone(),
+                    ));
+                }
+            } else {
+                self.push_autolinks(segment);
             }
             if iter.peek().is_some() {
                 self.current_block.push(StyledSegment::LineBreak);
@@ -69,45 +253,89 @@ impl<'a> MarkdownRenderer<'a> {
         }
     }
 
+    /// Pushes a line of text, making links of the bare web addresses in it.
+    fn push_autolinks(&mut self, mut line: &str) {
+        while !line.is_empty() {
+            let start = ["https://", "http://"]
+                .iter()
+                .filter_map(|scheme| line.find(scheme))
+                .min()
+                .unwrap_or(line.len());
+            if start > 0 {
+                self.current_block.push(StyledSegment::Text(
+                    line[..start].to_string(),
+                    self.current_style.clone(),
+                ));
+            }
+            line = &line[start..];
+            let end = line
+                .find(|c: char| c.is_whitespace() || "<>\"'`".contains(c))
+                .unwrap_or(line.len());
+            let url = trim_url(&line[..end]);
+            let link = url
+                .split_once("://")
+                .is_some_and(|(_, address)| !address.is_empty())
+                .then(|| url.into());
+            if !url.is_empty() {
+                let style = InlineStyle {
+                    link,
+                    ..self.current_style.clone()
+                };
+                self.current_block
+                    .push(StyledSegment::Text(url.to_string(), style));
+            }
+            line = &line[url.len()..];
+        }
+    }
+
     fn flush_block(&mut self) {
+        self.flush_text();
         if self.current_block.is_empty() {
             return;
         }
 
-        // Split block into words and breaks
-        let mut words = Vec::new();
-        for segment in &self.current_block {
+        // Split block into words, `None` standing for a line break
+        let mut words: Vec<Option<Word>> = Vec::new();
+        for segment in self.current_block.drain(..) {
             match segment {
+                // Inline code keeps its spaces.
+                StyledSegment::Text(text, style) if style.code => {
+                    words.push(Some(Word { text, style }));
+                }
                 StyledSegment::Text(content, style) => {
                     for word in content.split_whitespace() {
-                        words.push(StyledWord::Text(word, *style));
+                        words.push(Some(Word {
+                            text: word.to_string(),
+                            style: style.clone(),
+                        }));
                     }
                 }
-                StyledSegment::LineBreak => {
-                    words.push(StyledWord::Break);
-                }
+                StyledSegment::LineBreak => words.push(None),
             }
         }
 
-        // Group words into lines (max 80 chars per line)
-        let mut lines: Vec<Vec<StyledWord>> = Vec::new();
-        let mut current_line: Vec<StyledWord> = Vec::new();
+        // Group words into lines
+        let mut lines: Vec<Vec<Word>> = Vec::new();
+        let mut current_line: Vec<Word> = Vec::new();
         let mut current_line_len = 0;
-        const MAX_LINE_LENGTH: usize = 80;
+        let max_line_length = match self.containers.last() {
+            Some((Container::Cell, _)) => CELL_LINE_LENGTH,
+            _ => LINE_LENGTH,
+        };
 
         for word in words {
             match word {
-                StyledWord::Text(content, style) => {
-                    let word_len = content.len();
-                    if current_line_len + word_len > MAX_LINE_LENGTH && !current_line.is_empty() {
+                Some(word) => {
+                    let word_len = word.text.len();
+                    if current_line_len + word_len > max_line_length && !current_line.is_empty() {
                         lines.push(current_line);
                         current_line = Vec::new();
                         current_line_len = 0;
                     }
                     current_line_len += word_len + 1; // +1 for space
-                    current_line.push(StyledWord::Text(content, style));
+                    current_line.push(word);
                 }
-                StyledWord::Break => {
+                None => {
                     lines.push(current_line);
                     current_line = Vec::new();
                     current_line_len = 0;
@@ -118,76 +346,552 @@ impl<'a> MarkdownRenderer<'a> {
             lines.push(current_line);
         }
 
-        // Create a row for each line
-        for line in lines {
-            let mut row_children = Vec::new();
-            for (i, word) in line.iter().enumerate() {
-                if let StyledWord::Text(content, style) = word {
-                    row_children.push(style.apply(content, self.current_block_size));
-                    if i < line.len() - 1 {
-                        row_children.push(text(" ").size(self.current_block_size).into());
-                    }
+        let size = self.current_block_size;
+        self.push_block(Block::Text { lines, size });
+    }
+
+    fn push_code_block(&mut self, language: String, mut code: String) {
+        if code.ends_with('\n') {
+            code.pop();
+        }
+        let fragments = highlight(&language, &code);
+        self.push_block(Block::Code {
+            language,
+            code: code.into(),
+            fragments,
+        });
+    }
+
+    fn push_block(&mut self, block: Block) {
+        if let Some((_, blocks)) = self.containers.last_mut() {
+            blocks.push(block);
+        }
+    }
+
+    fn open(&mut self, container: Container) {
+        self.flush_block();
+        self.containers.push((container, Vec::new()));
+    }
+
+    /// Closes the innermost container, returning it with its blocks.
+    fn close(&mut self) -> (Container, Vec<Block>) {
+        self.flush_block();
+        self.containers
+            .pop()
+            .unwrap_or((Container::Document, Vec::new()))
+    }
+
+    fn close_block_quote(&mut self) {
+        let (_, quote) = self.close();
+        self.push_block(Block::Quote(quote));
+    }
+
+    fn close_list(&mut self) {
+        if let (Container::List { items, .. }, _) = self.close() {
+            self.push_block(Block::List(items));
+        }
+    }
+
+    /// Numbers the item in an ordered list, bullets it by depth otherwise.
+    fn close_item(&mut self) {
+        let (_, content) = self.close();
+        let depth = self
+            .containers
+            .iter()
+            .filter(|(container, _)| matches!(container, Container::List { .. }))
+            .count();
+        if let Some((Container::List { next, items }, _)) = self.containers.last_mut() {
+            let marker = match next {
+                Some(number) => {
+                    *number += 1;
+                    format!("{}.", *number - 1)
+                }
+                None => match depth {
+                    0 | 1 => "•",
+                    2 => "◦",
+                    _ => "▪",
                 }
+                .to_string(),
+            };
+            items.push((marker, content));
+        }
+    }
+
+    fn close_cell(&mut self) {
+        let chars = self
+            .current_block
+            .split(|segment| matches!(segment, StyledSegment::LineBreak))
+            .map(|line| {
+                line.iter()
+                    .map(|segment| match segment {
+                        StyledSegment::Text(content, _) => content.chars().count(),
+                        StyledSegment::LineBreak => 0,
+                    })
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap_or_default();
+        let (_, cell) = self.close();
+        if let Some((Container::Table { rows, .. }, _)) = self.containers.last_mut() {
+            if let Some(row) = rows.last_mut() {
+                row.push((cell, chars));
             }
-            self.elements.push(row(row_children).spacing(0).into());
         }
+    }
 
-        self.current_block.clear();
+    /// Starts a row of the table, in bold for the head.
+    fn start_row(&mut self, head: bool) {
+        if let Some((Container::Table { rows, .. }, _)) = self.containers.last_mut() {
+            rows.push(Vec::new());
+        }
+        self.current_style.bold = head;
     }
 
-    fn push_list_item(&mut self) {
-        self.current_block
-            .insert(0, StyledSegment::Text("• ", self.current_style));
+    fn close_table(&mut self) {
+        let (Container::Table { alignments, rows }, _) = self.close() else {
+            return;
+        };
+        let mut widths = vec![0; alignments.len()];
+        for row in &rows {
+            for (index, (_, chars)) in row.iter().enumerate() {
+                if index < widths.len() {
+                    widths[index] = widths[index].max((*chars).min(CELL_LINE_LENGTH));
+                }
+            }
+        }
+        let rows = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|(cell, _)| cell).collect())
+            .collect();
+        self.push_block(Block::Table {
+            alignments,
+            widths,
+            rows,
+        });
+    }
+
+    fn push_rule(&mut self) {
         self.flush_block();
+        self.push_block(Block::Rule);
     }
 
-    fn finish(self) -> Column<'a, Message> {
-        column(self.elements).spacing(10)
+    fn finish(mut self) -> Markdown {
+        self.flush_block();
+        // Unclosed containers only happen on malformed input, keep their content.
+        Markdown(
+            self.containers
+                .into_iter()
+                .flat_map(|(_, blocks)| blocks)
+                .collect(),
+        )
     }
 }
 
-pub fn markdown<'a>(content: &'a str) -> Element<'a, Message> {
-    let parser = Parser::new(content);
-    let mut renderer = MarkdownRenderer::new();
+impl Markdown {
+    pub fn parse(content: &str) -> Self {
+        let parser = Parser::new_ext(
+            content,
+            Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+        );
+        let mut builder = MarkdownBuilder::new();
 
-    for event in parser {
-        match event {
-            Event::Start(tag) => match tag {
-                Tag::Heading { level, .. } => {
-                    renderer.current_block_size = match level as i32 {
-                        1 => 26,
-                        2 => 22,
-                        3 => 18,
-                        4 => 16,
-                        5 => 14,
-                        _ => 12,
-                    };
+        for event in parser {
+            if !matches!(event, Event::Text(_)) {
+                builder.flush_text();
+            }
+            match event {
+                Event::Start(tag) => match tag {
+                    Tag::Heading { level, .. } => {
+                        builder.current_block_size = match level as i32 {
+                            1 => 26,
+                            2 => 22,
+                            3 => 18,
+                            4 => 16,
+                            5 => 14,
+                            _ => 12,
+                        };
+                    }
+                    Tag::CodeBlock(kind) => {
+                        builder.flush_block();
+                        let language = match kind {
+                            // The info string may carry attributes after the language.
+                            CodeBlockKind::Fenced(info) => info
+                                .split(|c: char| c.is_whitespace() || c == ',')
+                                .next()
+                                .unwrap_or_default()
+                                .to_string(),
+                            CodeBlockKind::Indented => String::new(),
+                        };
+                        builder.code_block = Some((language, String::new()));
+                    }
+                    Tag::BlockQuote(_) => builder.open(Container::BlockQuote),
+                    Tag::List(start) => builder.open(Container::List {
+                        next: start,
+                        items: Vec::new(),
+                    }),
+                    Tag::Item => builder.open(Container::Item),
+                    Tag::Table(alignments) => builder.open(Container::Table {
+                        alignments,
+                        rows: Vec::new(),
+                    }),
+                    Tag::TableHead => builder.start_row(true),
+                    Tag::TableRow => builder.start_row(false),
+                    Tag::TableCell => builder.open(Container::Cell),
+                    Tag::Emphasis => builder.current_style.italic = true,
+                    Tag::Strong => builder.current_style.bold = true,
+                    Tag::Strikethrough => builder.current_style.strikethrough = true,
+                    Tag::Link { dest_url, .. } => {
+                        builder.current_style.link = Some(dest_url.as_ref().into());
+                    }
+                    _ => {}
+                },
+                Event::End(tag) => match tag {
+                    TagEnd::Heading(..) => {
+                        builder.flush_block();
+                        builder.current_block_size = TEXT_SIZE;
+                    }
+                    TagEnd::Paragraph => builder.flush_block(),
+                    TagEnd::CodeBlock => {
+                        if let Some((language, code)) = builder.code_block.take() {
+                            builder.push_code_block(language, code);
+                        }
+                    }
+                    TagEnd::BlockQuote(_) => builder.close_block_quote(),
+                    TagEnd::List(_) => builder.close_list(),
+                    TagEnd::Item => builder.close_item(),
+                    TagEnd::TableHead => builder.current_style.bold = false,
+                    TagEnd::TableCell => builder.close_cell(),
+                    TagEnd::Table => builder.close_table(),
+                    TagEnd::Emphasis => builder.current_style.italic = false,
+                    TagEnd::Strong => builder.current_style.bold = false,
+                    TagEnd::Strikethrough => builder.current_style.strikethrough = false,
+                    TagEnd::Link => builder.current_style.link = None,
+                    _ => {}
+                },
+                Event::Text(txt) => match &mut builder.code_block {
+                    Some((_, code)) => code.push_str(&txt),
+                    None => builder.text.push_str(&txt),
+                },
+                Event::Code(txt) => {
+                    let style = builder.current_style.clone();
+                    builder.current_style.code = true;
+                    builder.push_text(&txt);
+                    builder.current_style = style;
                 }
-                Tag::Emphasis => renderer.current_style.italic = true,
-                Tag::Strong => renderer.current_style.bold = true,
-                _ => {}
-            },
-            Event::End(tag) => match tag {
-                TagEnd::Heading(..) => {
-                    renderer.flush_block();
-                    renderer.current_block_size = 14;
+                Event::SoftBreak | Event::HardBreak => {
+                    builder.current_block.push(StyledSegment::LineBreak);
                 }
-                TagEnd::Paragraph => renderer.flush_block(),
-                TagEnd::Item => renderer.push_list_item(),
-                TagEnd::Emphasis => renderer.current_style.italic = false,
-                TagEnd::Strong => renderer.current_style.bold = false,
+                Event::Rule => builder.push_rule(),
                 _ => {}
-            },
-            Event::Text(txt) | Event::Code(txt) => {
-                renderer.push_text(Box::leak(Box::new(txt)));
             }
-            Event::SoftBreak | Event::HardBreak => {
-                renderer.current_block.push(StyledSegment::LineBreak);
-            }
-            _ => {}
         }
+
+        builder.finish()
     }
+}
+
+/// Renders markdown parsed beforehand, borrowing its text.
+pub fn markdown(content: &Markdown) -> Element<'_, Message> {
+    blocks(&content.0).into()
+}
+
+fn blocks(blocks: &[Block]) -> Column<'_, Message> {
+    Column::with_children(blocks.iter().map(block)).spacing(10)
+}
+
+fn block(block: &Block) -> Element<'_, Message> {
+    match block {
+        Block::Text { lines, size } => {
+            Column::with_children(lines.iter().map(|line| text_line(line, *size)))
+                .spacing(10)
+                .into()
+        }
+        Block::Code {
+            language,
+            code,
+            fragments,
+        } => code_block(language, code, fragments),
+        Block::Quote(quote) => block_quote(quote),
+        Block::List(items) => list(items),
+        Block::Table {
+            alignments,
+            widths,
+            rows,
+        } => table(alignments, widths, rows),
+        Block::Rule => container(Space::new(Length::Fixed(RULE_WIDTH), Length::Fixed(1.0)))
+            .style(|_| container::Style::default().background(Color::from(FOOTER_GRAY)))
+            .into(),
+    }
+}
+
+fn text_line(line: &[Word], size: u16) -> Element<'_, Message> {
+    let mut row_children = Vec::new();
+    for (i, word) in line.iter().enumerate() {
+        row_children.push(word.style.apply(&word.text, size));
+        if i < line.len() - 1 {
+            row_children.push(text(" ").size(size).into());
+        }
+    }
+    row(row_children).spacing(0).into()
+}
+
+/// Shows the code verbatim in a monospace box scrolling sideways.
+fn code_block<'a>(
+    language: &'a str,
+    code: &Arc<str>,
+    fragments: &'a [(Color, String)],
+) -> Element<'a, Message> {
+    let spans = fragments
+        .iter()
+        .map(|(color, fragment)| span(fragment).color(*color))
+        .collect::<Vec<_>>();
+    let header = row![
+        text(language).size(11).color(Color::from(FOOTER_GRAY)),
+        copy_button(Message::Copy(code.clone())),
+    ]
+    .spacing(10);
+    let block = column![header].spacing(5).push(
+        scrollable(
+            container(
+                rich_text(spans)
+                    .font(Font::MONOSPACE)
+                    .size(13)
+                    .wrapping(Wrapping::None),
+            )
+            .padding(Padding::ZERO.bottom(10)),
+        )
+        .direction(Direction::Horizontal(Scrollbar::new())),
+    );
+    container(block)
+        .padding(10)
+        .style(|_| styles::card(CODE_BACKGROUND))
+        .into()
+}
+
+/// Quoted blocks, the left padding of the bar color showing as a side bar.
+fn block_quote(quote: &[Block]) -> Element<'_, Message> {
+    container(
+        container(blocks(quote))
+            .padding([5, 10])
+            .style(|_| container::Style::default().background(Color::from(QUOTE_BACKGROUND))),
+    )
+    .padding(Padding::ZERO.left(3))
+    .style(|_| container::Style::default().background(Color::from(QUOTE_BAR)))
+    .into()
+}
+
+fn list(items: &[(String, Vec<Block>)]) -> Element<'_, Message> {
+    container(
+        Column::with_children(items.iter().map(|(marker, content)| {
+            row![text(marker).size(TEXT_SIZE), blocks(content).spacing(5)]
+                .spacing(5)
+                .into()
+        }))
+        .spacing(5),
+    )
+    .padding(Padding::ZERO.left(10))
+    .into()
+}
 
-    renderer.flush_block();
-    renderer.finish().into()
+/// Lays the cells out in columns as wide as their longest text.
+fn table<'a>(
+    alignments: &[Alignment],
+    widths: &[usize],
+    rows: &'a [Vec<Vec<Block>>],
+) -> Element<'a, Message> {
+    let table = Column::with_children(rows.iter().enumerate().map(|(index, row)| {
+        let cells = Row::with_children(row.iter().enumerate().map(|(column, cell)| {
+            let width = widths.get(column).copied().unwrap_or(CELL_LINE_LENGTH);
+            container(blocks(cell))
+                .width(Length::Fixed(width.max(3) as f32 * CHAR_WIDTH + 10.0))
+                .padding(5)
+                .align_x(match alignments.get(column) {
+                    Some(Alignment::Center) => Horizontal::Center,
+                    Some(Alignment::Right) => Horizontal::Right,
+                    _ => Horizontal::Left,
+                })
+                .into()
+        }));
+        let background = if index == 0 { TABLE_HEADER } else { WHITE };
+        container(cells)
+            .style(move |_| container::Style::default().background(Color::from(background)))
+            .into()
+    }))
+    .spacing(1);
+    container(table)
+        .padding(1)
+        .style(|_| container::Style::default().background(Color::from(FOOTER_GRAY)))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Words of a text block, lines joined by a newline.
+    fn text_of(block: &Block) -> String {
+        let Block::Text { lines, .. } = block else {
+            panic!("not a text block: {block:?}");
+        };
+        lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|word| word.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn markers(block: &Block) -> Vec<&str> {
+        let Block::List(items) = block else {
+            panic!("not a list: {block:?}");
+        };
+        items.iter().map(|(marker, _)| marker.as_str()).collect()
+    }
+
+    #[test]
+    fn table_head_in_bold_with_column_widths() {
+        let Markdown(blocks) =
+            Markdown::parse("| Name | Qty |\n|------|:---:|\n| apples and pears | 3 |");
+        let [Block::Table {
+            alignments,
+            widths,
+            rows,
+        }] = blocks.as_slice()
+        else {
+            panic!("not a single table: {blocks:?}");
+        };
+        assert_eq!(alignments, &[Alignment::None, Alignment::Center]);
+        assert_eq!(widths, &[16, 3]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(text_of(&rows[0][0][0]), "Name");
+        assert_eq!(text_of(&rows[1][0][0]), "apples and pears");
+        let bold = |cell: &[Block]| match &cell[0] {
+            Block::Text { lines, .. } => lines.iter().flatten().all(|word| word.style.bold),
+            _ => false,
+        };
+        assert!(rows[0].iter().all(|cell| bold(cell)));
+        assert!(!rows[1].iter().any(|cell| bold(cell)));
+    }
+
+    #[test]
+    fn long_cells_keep_tables_narrow() {
+        let cell = "word ".repeat(20);
+        let Markdown(blocks) = Markdown::parse(&format!("| A |\n|---|\n| {cell} |"));
+        let [Block::Table { widths, .. }] = blocks.as_slice() else {
+            panic!("not a single table: {blocks:?}");
+        };
+        assert_eq!(widths, &[CELL_LINE_LENGTH]);
+    }
+
+    #[test]
+    fn ordered_list_numbered_from_its_start() {
+        let Markdown(blocks) = Markdown::parse("3. three\n4. four\n5. five");
+        assert_eq!(markers(&blocks[0]), ["3.", "4.", "5."]);
+    }
+
+    #[test]
+    fn nested_bullets_by_depth() {
+        let Markdown(blocks) = Markdown::parse("- one\n  - two\n    - three\n- four");
+        assert_eq!(markers(&blocks[0]), ["•", "•"]);
+        let Block::List(items) = &blocks[0] else {
+            unreachable!()
+        };
+        let (_, first) = &items[0];
+        assert_eq!(text_of(&first[0]), "one");
+        assert_eq!(markers(&first[1]), ["◦"]);
+        let Block::List(nested) = &first[1] else {
+            unreachable!()
+        };
+        assert_eq!(markers(&nested[0].1[1]), ["▪"]);
+    }
+
+    #[test]
+    fn block_quote_holds_its_blocks() {
+        let Markdown(blocks) = Markdown::parse("> quoted\n> - item\n\nafter");
+        let [Block::Quote(quote), after] = blocks.as_slice() else {
+            panic!("not a quote then text: {blocks:?}");
+        };
+        assert_eq!(text_of(&quote[0]), "quoted");
+        assert_eq!(markers(&quote[1]), ["•"]);
+        assert_eq!(text_of(after), "after");
+    }
+
+    #[test]
+    fn rule_between_paragraphs() {
+        let Markdown(blocks) = Markdown::parse("before\n\n---\n\nafter");
+        assert!(matches!(
+            blocks.as_slice(),
+            [Block::Text { .. }, Block::Rule, Block::Text { .. }]
+        ));
+    }
+
+    /// Text and target of the links of a paragraph.
+    fn links(markdown: &str) -> Vec<(String, String)> {
+        let Markdown(blocks) = Markdown::parse(markdown);
+        let Some(Block::Text { lines, .. }) = blocks.first() else {
+            panic!("not a paragraph: {blocks:?}");
+        };
+        lines
+            .iter()
+            .flatten()
+            .filter_map(|word| Some((word.text.clone(), word.style.link.as_deref()?.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn autolinks_addresses_split_by_the_parser() {
+        let url = "https://en.wikipedia.org/wiki/Rust_(programming_language)";
+        assert_eq!(
+            links(&format!("see {url} now")),
+            [(url.to_string(), url.to_string())]
+        );
+        let url = "https://example.com/some_file_name?a=1&b=2*";
+        assert_eq!(
+            links(&format!("at {url}")),
+            [(url.to_string(), url.to_string())]
+        );
+    }
+
+    #[test]
+    fn autolinks_leave_out_closing_punctuation() {
+        let url = "https://example.com/page".to_string();
+        assert_eq!(
+            links(&format!("(see {url}).")),
+            [(url.clone(), url.clone())]
+        );
+        assert_eq!(links(&format!("Go to {url}!")), [(url.clone(), url)]);
+        let url = "https://example.com/a_(b)".to_string();
+        assert_eq!(links(&format!("({url})")), [(url.clone(), url)]);
+    }
+
+    #[test]
+    fn autolinks_keep_the_words_around() {
+        let Markdown(blocks) = Markdown::parse("see https://example.com/x_y now");
+        assert_eq!(text_of(&blocks[0]), "see https://example.com/x_y now");
+    }
+
+    #[test]
+    fn highlights_known_languages() {
+        let fragments = highlight("rust", "fn main() {}\n");
+        assert_eq!(
+            fragments
+                .iter()
+                .map(|(_, text)| text.as_str())
+                .collect::<String>(),
+            "fn main() {}\n"
+        );
+        assert!(fragments.len() > 1);
+        assert_ne!(fragments[0].0, fragments[1].0);
+    }
+
+    #[test]
+    fn unknown_languages_stay_plain() {
+        let fragments = highlight("no-such-language", "let x = 1;\nlet y = 2;");
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].1, "let x = 1;\nlet y = 2;");
+    }
 }
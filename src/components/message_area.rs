@@ -1,22 +1,54 @@
 use iced::{
+    clipboard,
+    time::Duration,
     widget::{column, container, row, scrollable, text, Column, Space},
-    Alignment, Color, Element, Length,
+    Alignment, Color, Element, Length, Task,
 };
 
 use crate::{
-    styles::{self, AI_LABEL_COLOR, BLUE_SKY, GRAY},
-    types::MessageType,
-    AIChoice, Message,
+    models::ModelPrice,
+    services,
+    styles::{self, AI_LABEL_COLOR, BLUE_SKY, ERROR_RED, FOOTER_GRAY, GRAY},
+    types::{AIChoice, FinishReason, MessageType, SafetyRating, Usage},
+    Message, State,
 };
 
-use super::markdown::markdown;
+use super::{
+    attachments,
+    button::{copy_button, rounded_button},
+    markdown::{markdown, Markdown},
+};
 
-pub fn chat_area<'l>(messages: Vec<(MessageType, String)>) -> Element<'l, Message> {
+/// `excluded` messages were left out of the last request, or `summarized`.
+pub fn chat_area<'l>(
+    messages: Vec<(MessageType, &'l str, &'l Markdown)>,
+    prices: &[ModelPrice],
+    excluded: &[usize],
+    summarized: bool,
+) -> Element<'l, Message> {
+    let last = messages.len().saturating_sub(1);
+    let exclusion = if summarized { "summarized" } else { "not sent" };
     container(
         scrollable(
-            column(messages.iter().map(|(message_type, content)| {
-                create_chat_bubble(message_type.clone(), content.clone())
-            }))
+            column(
+                messages
+                    .iter()
+                    .enumerate()
+                    .map(
+                        |(i, (message_type, content, markdown))| match message_type {
+                            MessageType::Error => error_bubble(content, i == last),
+                            _ => create_chat_bubble(
+                                i,
+                                message_type.clone(),
+                                content,
+                                markdown,
+                                prices,
+                                excluded.contains(&i).then_some(exclusion),
+                                i == last,
+                            ),
+                        },
+                    ),
+            )
             .spacing(10),
         )
         .height(Length::Fill),
@@ -24,51 +56,199 @@ pub fn chat_area<'l>(messages: Vec<(MessageType, String)>) -> Element<'l, Messag
     .into()
 }
 
-fn create_chat_bubble<'l>(message_type: MessageType, content: String) -> Element<'l, Message> {
-    let author = match message_type {
-        MessageType::Received(ref choice) => match choice {
-            AIChoice::Gemini(_, _) => column!(text("@gemini").color(Color::from(AI_LABEL_COLOR))),
-            AIChoice::Mistral => {
-                column!(text("@mistral").color(Color::from(AI_LABEL_COLOR)))
-            }
-        },
-        _ => column!(),
+/// Token counts of a reply, with its cost when the model has a price.
+pub fn usage_label(usage: Usage, cost: Option<f64>) -> String {
+    let count = |tokens: Option<u32>| tokens.map_or("?".to_string(), |tokens| tokens.to_string());
+    let mut label = format!(
+        "{} in · {} out",
+        count(usage.input_tokens),
+        count(usage.output_tokens)
+    );
+    if let Some(cost) = cost {
+        label.push_str(&format!(" · ${cost:.4}"));
+    }
+    label
+}
+
+/// Bubble of the message at `index`, which its copy button sends.
+fn create_chat_bubble<'l>(
+    index: usize,
+    message_type: MessageType,
+    content: &'l str,
+    markdown: &'l Markdown,
+    prices: &[ModelPrice],
+    exclusion: Option<&str>,
+    is_last: bool,
+) -> Element<'l, Message> {
+    let label = match message_type {
+        MessageType::Received(ref choice, _, ref finish) => {
+            Some(match finish.reason.as_ref().and_then(FinishReason::label) {
+                Some(reason) => format!("@{} · {reason}", choice.provider),
+                None => format!("@{}", choice.provider),
+            })
+        }
+        MessageType::Stopped(ref choice, _) => Some(format!("@{} · stopped", choice.provider)),
+        _ => None,
+    };
+    let label = match (label, exclusion) {
+        (Some(label), Some(exclusion)) => Some(format!("{label} · {exclusion}")),
+        (label, exclusion) => label.or(exclusion.map(str::to_string)),
+    };
+    let author = match label {
+        Some(label) => column!(text(label).color(Color::from(AI_LABEL_COLOR))),
+        None => column!(),
     };
+    let mut footer = Column::new().spacing(5);
+    footer = footer.push(copy_button(Message::CopyMessage(index)));
+    if let MessageType::Received(ref choice, usage, _) | MessageType::Stopped(ref choice, usage) =
+        message_type
+    {
+        if !usage.is_empty() {
+            let cost = price_of(prices, choice).and_then(|price| price.cost(usage));
+            footer = footer.push(
+                text(usage_label(usage, cost))
+                    .size(11)
+                    .color(Color::from(FOOTER_GRAY)),
+            );
+        }
+    }
+    if let MessageType::Received(_, _, ref finish) = message_type {
+        if let Some(ratings) = safety_label(&finish.safety_ratings) {
+            footer = footer.push(text(ratings).size(11).color(Color::from(FOOTER_GRAY)));
+        }
+    }
+    if let MessageType::Sent(ref attachments) = message_type {
+        if !attachments.is_empty() {
+            footer = footer.push(attachments::chips(attachments, false));
+        }
+    }
+    // Only the last reply can be continued, a later one would answer the request.
+    if is_last && message_type.cut_off() {
+        footer = footer.push(rounded_button(
+            "Continue",
+            Message::Continue,
+            |_, status| styles::primary_button(status),
+        ));
+    }
 
     match message_type {
-        MessageType::Sent => row![
+        MessageType::Sent(_) => row![
             Space::with_width(Length::Fill),
-            bubble_message(author, content, message_type)
+            bubble_message(author, markdown, message_type, footer)
         ]
         .spacing(10)
         .align_y(Alignment::End)
         .padding(20)
         .into(),
-        MessageType::Received(_) => row![
-            bubble_message(author, content, message_type),
+        MessageType::Received(..) | MessageType::Stopped(..) => row![
+            bubble_message(author, markdown, message_type, footer),
             Space::with_width(Length::Fill)
         ]
         .spacing(10)
         .align_y(Alignment::Start)
         .padding(20)
         .into(),
+        MessageType::Error => error_bubble(content, false),
+    }
+}
+
+/// Safety ratings worth showing, those above `NEGLIGIBLE` or that blocked the reply.
+fn safety_label(ratings: &[SafetyRating]) -> Option<String> {
+    let ratings = ratings
+        .iter()
+        .filter(|rating| rating.blocked || rating.probability != "NEGLIGIBLE")
+        .map(|rating| {
+            let category = rating
+                .category
+                .strip_prefix("HARM_CATEGORY_")
+                .unwrap_or(&rating.category)
+                .replace('_', " ")
+                .to_lowercase();
+            let blocked = if rating.blocked { ", blocked" } else { "" };
+            format!("{category}: {}{blocked}", rating.probability.to_lowercase())
+        })
+        .collect::<Vec<_>>();
+    (!ratings.is_empty()).then(|| format!("Safety: {}", ratings.join(" · ")))
+}
+
+/// Only the last error can be retried, it is the one answering the last prompt.
+fn error_bubble(content: &str, retry: bool) -> Element<'_, Message> {
+    let mut bubble = column![
+        text("error").color(Color::from(AI_LABEL_COLOR)),
+        text(content)
+    ]
+    .spacing(5);
+    if retry {
+        bubble = bubble.push(rounded_button("Retry", Message::Retry, |_, status| {
+            styles::primary_button(status)
+        }));
     }
+
+    row![
+        container(bubble)
+            .padding(10)
+            .style(|_| styles::card(ERROR_RED))
+            .width(Length::Shrink),
+        Space::with_width(Length::Fill)
+    ]
+    .padding(20)
+    .into()
 }
 
-fn bubble_message(
-    author: Column<'_, Message>,
-    content: String,
+fn bubble_message<'l>(
+    author: Column<'l, Message>,
+    content: &'l Markdown,
     message_type: MessageType,
-) -> Element<Message> {
-    container(column![
-        author,
-        markdown(Box::leak(content.into_boxed_str()))
+    footer: Column<'l, Message>,
+) -> Element<'l, Message> {
+    container(column![author, markdown(content), footer])
+        .padding(10)
+        .style(match message_type.clone() {
+            MessageType::Sent(_) => |_: &iced::Theme| styles::card(BLUE_SKY),
+            MessageType::Received(..) | MessageType::Stopped(..) | MessageType::Error => {
+                |_: &iced::Theme| styles::card(GRAY)
+            }
+        })
+        .width(Length::Shrink)
+        .into()
+}
+
+/// Copies `content` and confirms it for a moment.
+pub fn copy(content: String) -> Task<Message> {
+    Task::batch([
+        clipboard::write(content),
+        Task::done(Message::DisplayMessage {
+            duration: Duration::from_secs(2),
+            msg: "Copied to the clipboard".to_string(),
+        }),
     ])
-    .padding(10)
-    .style(match message_type.clone() {
-        MessageType::Sent => |_: &iced::Theme| styles::card(BLUE_SKY),
-        MessageType::Received(_) => |_: &iced::Theme| styles::card(GRAY),
+}
+
+/// Copies the text of the shown message at `index`, the pending reply last.
+pub fn copy_message(state: &State, index: usize) -> Task<Message> {
+    let content = match state.messages.get(index) {
+        Some((_, content, _)) => Some(content),
+        None => state
+            .pending
+            .as_ref()
+            .filter(|_| index == state.messages.len())
+            .map(|(_, content, ..)| content),
+    };
+    content.map_or_else(Task::none, |content| copy(content.clone()))
+}
+
+/// Opens `url` in the browser, telling when it fails.
+pub fn open_link(url: String) -> Task<Message> {
+    Task::future(services::open_link(url)).then(|result| match result {
+        Ok(()) => Task::none(),
+        Err(msg) => Task::done(Message::DisplayMessage {
+            duration: Duration::from_secs(4),
+            msg,
+        }),
     })
-    .width(Length::Shrink)
-    .into()
+}
+
+pub fn price_of<'p>(prices: &'p [ModelPrice], choice: &AIChoice) -> Option<&'p ModelPrice> {
+    let model = choice.to_string();
+    prices.iter().find(|price| price.model == model)
 }
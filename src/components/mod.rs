@@ -1,6 +1,9 @@
+pub mod attachments;
 pub mod button;
 pub mod input_form;
 pub mod markdown;
 pub mod message_area;
 pub mod nav_bar;
+pub mod params_panel;
+pub mod sidebar;
 pub mod text_input;
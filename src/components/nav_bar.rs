@@ -9,6 +9,9 @@ use super::button::rounded_button;
 
 pub fn nav_bar<'l>() -> Element<'l, Message> {
     row![
+        rounded_button("chats", Message::ToggleSidebar, |_, status| {
+            styles::primary_button(status)
+        }),
         Space::with_width(Length::Fill),
         rounded_button(
             "setting",
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use iced::{
+    widget::{column, row, text, text_input, Row},
+    Color, Element, Task,
+};
+use rusql_alchemy::prelude::Integer;
+
+use crate::{
+    models::Conversation,
+    styles,
+    types::{FormState, GenerationParams, Preset},
+    Message, State,
+};
+
+use super::{button::rounded_button, input_form::input_form};
+
+/// Quiet time after an edit before the conversation is saved.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Keys of the panel fields with their placeholders.
+const FIELDS: &[(&str, &str)] = &[
+    ("temperature", "Temperature"),
+    ("top_p", "Top P"),
+    ("top_k", "Top K"),
+    ("max_tokens", "Max tokens"),
+    ("stop", "Stop sequences, comma separated"),
+    ("seed", "Seed"),
+];
+
+pub fn params_panel(state: &State) -> Element<'_, Message> {
+    let presets = Row::with_children(Preset::ALL.map(|preset| {
+        let selected = preset.apply(&state.params) == state.params;
+        rounded_button(
+            preset.label(),
+            Message::ApplyPreset(preset),
+            move |_, status| {
+                if selected {
+                    styles::primary_button(status)
+                } else {
+                    styles::secondary_button(status)
+                }
+            },
+        )
+    }))
+    .spacing(5);
+
+    let fields = Row::with_children(FIELDS.iter().map(|(key, placeholder)| {
+        input_form(
+            placeholder,
+            key,
+            &state.param_inputs,
+            |value| Message::InputParam {
+                key: key.to_string(),
+                value,
+            },
+            false,
+        )
+    }))
+    .spacing(5);
+
+    let system_prompt = text_input("System prompt, e.g. answer in French", &state.system_prompt)
+        .style(styles::rounded_input)
+        .on_input(Message::InputSystemPrompt)
+        .padding(10)
+        .size(16);
+
+    column![
+        system_prompt,
+        row![text("Presets").color(Color::BLACK), presets].spacing(10),
+        fields,
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// Text of the panel fields, empty for the provider defaults.
+pub fn inputs_of(params: &GenerationParams) -> FormState {
+    fn field<T: ToString>(value: Option<T>) -> String {
+        value.map(|value| value.to_string()).unwrap_or_default()
+    }
+    FormState::from([
+        ("temperature".to_string(), field(params.temperature)),
+        ("top_p".to_string(), field(params.top_p)),
+        ("top_k".to_string(), field(params.top_k)),
+        ("max_tokens".to_string(), field(params.max_tokens)),
+        ("stop".to_string(), params.stop.join(", ")),
+        ("seed".to_string(), field(params.seed)),
+    ])
+}
+
+/// Parses the panel fields, an empty or invalid field is left unset.
+fn params_of(inputs: &FormState) -> GenerationParams {
+    fn field<T: std::str::FromStr>(inputs: &FormState, key: &str) -> Option<T> {
+        inputs.get(key)?.trim().parse().ok()
+    }
+    GenerationParams {
+        temperature: field(inputs, "temperature"),
+        top_p: field(inputs, "top_p"),
+        top_k: field(inputs, "top_k"),
+        max_tokens: field(inputs, "max_tokens"),
+        stop: inputs
+            .get("stop")
+            .map(|stop| {
+                stop.split(',')
+                    .map(str::trim)
+                    .filter(|stop| !stop.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        seed: field(inputs, "seed"),
+    }
+}
+
+pub fn toggle_params(state: &mut State) -> Task<Message> {
+    state.params_open = !state.params_open;
+    Task::none()
+}
+
+pub fn input_param(state: &mut State, key: String, value: String) -> Task<Message> {
+    state.param_inputs.insert(key, value);
+    state.params = params_of(&state.param_inputs);
+    save_params(state)
+}
+
+pub fn apply_preset(state: &mut State, preset: Preset) -> Task<Message> {
+    state.set_params(preset.apply(&state.params));
+    save_params(state)
+}
+
+pub fn input_system_prompt(state: &mut State, system_prompt: String) -> Task<Message> {
+    state.system_prompt = system_prompt;
+    let Some(id) = state.conversation else {
+        return Task::none();
+    };
+    if let Some(conversation) = state
+        .conversations
+        .iter_mut()
+        .find(|conversation| conversation.id == id)
+    {
+        conversation.system_prompt = Some(state.system_prompt.clone());
+    }
+    schedule_save(state, id)
+}
+
+/// Keeps the parameters on the shown conversation, a new one gets them when created.
+fn save_params(state: &mut State) -> Task<Message> {
+    let Some(id) = state.conversation else {
+        return Task::none();
+    };
+    let params = serde_json::to_string(&state.params).unwrap_or_default();
+    if let Some(conversation) = state
+        .conversations
+        .iter_mut()
+        .find(|conversation| conversation.id == id)
+    {
+        conversation.params = Some(params);
+    }
+    schedule_save(state, id)
+}
+
+/// Saves the conversation once the edits pause, so only the last of a burst of
+/// keystrokes is written.
+fn schedule_save(state: &mut State, id: Integer) -> Task<Message> {
+    let edit = state.conversation_edits.entry(id).or_default();
+    *edit += 1;
+    let edit = *edit;
+    Task::future(async move {
+        tokio::time::sleep(SAVE_DELAY).await;
+        Message::SaveConversation { id, edit }
+    })
+}
+
+/// Writes the settings kept on the conversation, unless it was edited again since.
+pub fn save_conversation(state: &mut State, id: Integer, edit: u64) -> Task<Message> {
+    if state.conversation_edits.get(&id) != Some(&edit) {
+        return Task::none();
+    }
+    let Some(conversation) = state
+        .conversations
+        .iter()
+        .find(|conversation| conversation.id == id)
+    else {
+        return Task::none();
+    };
+    let system_prompt = conversation.system_prompt.clone();
+    let params = conversation.params.clone();
+
+    let conn = state.conn.clone();
+    Task::future(async move {
+        if let Some(system_prompt) = system_prompt {
+            Conversation::set_system_prompt(id, &system_prompt, &conn).await?;
+        }
+        if let Some(params) = params {
+            Conversation::set_params(id, &params, &conn).await?;
+        }
+        Ok::<_, sqlx::Error>(())
+    })
+    .then(|result| match result {
+        Ok(()) => Task::none(),
+        Err(err) => Task::done(Message::DisplayMessage {
+            duration: Duration::from_secs(4),
+            msg: format!("The conversation settings were not saved: {err}"),
+        }),
+    })
+}
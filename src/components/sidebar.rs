@@ -0,0 +1,145 @@
+use iced::{
+    widget::{button, column, row, scrollable, text, text_input, Column},
+    Element, Length, Task,
+};
+use rusql_alchemy::prelude::Integer;
+
+use crate::{models::Conversation, styles, Message, State};
+
+use super::button::rounded_button;
+
+const SIDEBAR_WIDTH: f32 = 250.0;
+
+pub fn sidebar(state: &State) -> Element<'_, Message> {
+    let conversations = Column::with_children(
+        state
+            .conversations
+            .iter()
+            .map(|conversation| conversation_entry(state, conversation)),
+    )
+    .spacing(5);
+
+    column![
+        rounded_button("New chat", Message::NewConversation, |_, status| {
+            styles::primary_button(status)
+        }),
+        scrollable(conversations).height(Length::Fill),
+    ]
+    .spacing(10)
+    .padding(10)
+    .width(SIDEBAR_WIDTH)
+    .height(Length::Fill)
+    .into()
+}
+
+fn conversation_entry<'l>(
+    state: &'l State,
+    conversation: &'l Conversation,
+) -> Element<'l, Message> {
+    let id = conversation.id;
+
+    if let Some((_, title)) = state
+        .renaming
+        .as_ref()
+        .filter(|(renamed, _)| *renamed == id)
+    {
+        return text_input("Title", title)
+            .style(styles::rounded_input)
+            .on_input(Message::InputRename)
+            .on_submit(Message::SaveRename)
+            .padding(5)
+            .into();
+    }
+
+    if state.confirm_delete == Some(id) {
+        return column![
+            text(format!("Delete \"{}\"?", conversation.title)).size(13),
+            row![
+                rounded_button("Delete", Message::ConfirmDelete, |_, status| {
+                    styles::danger_button(status)
+                }),
+                rounded_button("Cancel", Message::CancelDelete, |_, status| {
+                    styles::primary_button(status)
+                }),
+            ]
+            .spacing(5),
+        ]
+        .spacing(5)
+        .into();
+    }
+
+    let current = state.conversation == Some(id);
+    row![
+        button(text(&conversation.title).size(14))
+            .on_press(Message::OpenConversation(id))
+            .width(Length::Fill)
+            .style(move |theme, status| if current {
+                styles::primary_button(status)
+            } else {
+                button::text(theme, status)
+            }),
+        button(text("rename").size(12))
+            .on_press(Message::StartRename(id))
+            .style(button::text),
+        button(text("delete").size(12))
+            .on_press(Message::DeleteConversation(id))
+            .style(button::text),
+    ]
+    .into()
+}
+
+pub fn toggle_sidebar(state: &mut State) -> Task<Message> {
+    state.sidebar_open = !state.sidebar_open;
+    Task::none()
+}
+
+pub fn start_rename(state: &mut State, id: Integer) -> Task<Message> {
+    state.renaming = state
+        .conversations
+        .iter()
+        .find(|conversation| conversation.id == id)
+        .map(|conversation| (id, conversation.title.clone()));
+    Task::none()
+}
+
+pub fn input_rename(state: &mut State, title: String) -> Task<Message> {
+    if let Some((_, renamed)) = state.renaming.as_mut() {
+        *renamed = title;
+    }
+    Task::none()
+}
+
+/// Saves the typed title, an empty one keeps the previous title.
+pub fn save_rename(state: &mut State) -> Task<Message> {
+    let Some((id, title)) = state.renaming.take() else {
+        return Task::none();
+    };
+    let title = title.trim().to_string();
+    let Some(conversation) = state
+        .conversations
+        .iter_mut()
+        .find(|conversation| conversation.id == id)
+    else {
+        return Task::none();
+    };
+    if title.is_empty() {
+        return Task::none();
+    }
+    conversation.title = title.clone();
+
+    let conn = state.conn.clone();
+    Task::future(async move {
+        Conversation::rename(id, &title, &conn).await.ok();
+    })
+    .discard()
+}
+
+pub fn ask_delete(state: &mut State, id: Integer) -> Task<Message> {
+    state.confirm_delete = Some(id);
+    Task::none()
+}
+
+pub fn cancel_delete(state: &mut State) -> Task<Message> {
+    state.confirm_delete = None;
+    Task::none()
+}
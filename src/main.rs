@@ -7,16 +7,18 @@ mod styles;
 mod types;
 mod utils;
 
-use components::{input_form, nav_bar, text_input};
+use components::{
+    attachments, input_form, message_area, nav_bar, params_panel, sidebar, text_input,
+};
 use models::Config;
 use screens::{chat_screen, setting_screen};
 
 use iced::time::{self, Duration};
-use iced::{Element, Subscription, Task};
+use iced::{event, window, Element, Event, Subscription, Task};
 use state::State;
 use types::{AIChoice, Message, Screen};
 
-fn view(state: &State) -> Element<Message> {
+fn view(state: &State) -> Element<'_, Message> {
     match state.screen {
         Screen::ChatScreen => chat_screen::chat(state),
         Screen::SettingScreen => setting_screen::setting(state),
@@ -26,14 +28,62 @@ fn view(state: &State) -> Element<Message> {
 fn update(state: &mut State, message: Message) -> Task<Message> {
     match message {
         Message::Submit => chat_screen::action_submit(state),
-        Message::AIRespond(response) => chat_screen::handle_ai_response(state, response),
+        Message::AIDelta(delta) => chat_screen::handle_ai_delta(state, delta),
+        Message::AIDone => chat_screen::handle_ai_done(state),
+        Message::AIFailed(err) => chat_screen::handle_ai_failure(state, err),
+        Message::Summarized { context, summary } => {
+            chat_screen::handle_summarized(state, context, summary)
+        }
+        Message::Stop => chat_screen::action_stop(state),
+        Message::Retry => chat_screen::action_retry(state),
+        Message::Continue => chat_screen::action_continue(state),
+        Message::PickAttachments => attachments::pick_attachments(),
+        Message::AttachmentsPicked(paths) => attachments::attach_all(paths),
+        Message::FileDropped(path) => attachments::attach(path),
+        Message::Attached(attachment) => attachments::handle_attached(state, attachment),
+        Message::RemoveAttachment(index) => attachments::remove_attachment(state, index),
+        Message::Copy(content) => message_area::copy(content.to_string()),
+        Message::CopyMessage(index) => message_area::copy_message(state, index),
+        Message::OpenLink(url) => message_area::open_link(url.to_string()),
+        Message::ToggleSidebar => sidebar::toggle_sidebar(state),
+        Message::NewConversation => chat_screen::action_new_conversation(state),
+        Message::OpenConversation(id) => chat_screen::action_open_conversation(state, id),
+        Message::ConversationLoaded(id, messages) => {
+            chat_screen::handle_conversation_loaded(state, id, messages)
+        }
+        Message::StartRename(id) => sidebar::start_rename(state, id),
+        Message::InputRename(title) => sidebar::input_rename(state, title),
+        Message::SaveRename => sidebar::save_rename(state),
+        Message::DeleteConversation(id) => sidebar::ask_delete(state, id),
+        Message::ConfirmDelete => chat_screen::action_delete_conversation(state),
+        Message::CancelDelete => sidebar::cancel_delete(state),
         Message::InputTextArea(action) => text_input::handle_text_area_input(state, action),
         Message::InputForm { key, value } => {
             input_form::get_input_form(&mut state.forms, key, value)
         }
         Message::Selected(choice) => chat_screen::handle_choice(state, choice),
+        Message::ToggleParams => params_panel::toggle_params(state),
+        Message::InputSystemPrompt(system_prompt) => {
+            params_panel::input_system_prompt(state, system_prompt)
+        }
+        Message::InputParam { key, value } => params_panel::input_param(state, key, value),
+        Message::ApplyPreset(preset) => params_panel::apply_preset(state, preset),
+        Message::SaveConversation { id, edit } => params_panel::save_conversation(state, id, edit),
+        Message::RefreshModels => chat_screen::list_models(state, true),
+        Message::ModelsListed {
+            provider,
+            models,
+            refresh,
+        } => chat_screen::handle_models_listed(state, provider, models, refresh),
         Message::Route(screen) => nav_bar::router_pushed(state, screen),
         Message::SaveSetting => setting_screen::save_setting(state),
+        Message::InputPrice {
+            index,
+            output,
+            value,
+        } => setting_screen::handle_price_input(state, index, output, value),
+        Message::AddPrice => setting_screen::add_price(state),
+        Message::RemovePrice(index) => setting_screen::remove_price(state, index),
         Message::DisplayMessage { duration, msg } => {
             state.timer_enabled = true;
             state.message = msg;
@@ -54,14 +104,25 @@ fn update(state: &mut State, message: Message) -> Task<Message> {
 }
 
 fn subscription(state: &State) -> Subscription<Message> {
-    if state.timer_enabled {
+    let timer = if state.timer_enabled {
         time::every(Duration::from_secs(1)).map(|_| Message::Tick)
     } else {
         Subscription::none()
-    }
+    };
+    let dropped_files = event::listen_with(|event, _, _| match event {
+        Event::Window(window::Event::FileDropped(path)) => Some(Message::FileDropped(path)),
+        _ => None,
+    });
+    Subscription::batch([timer, dropped_files])
 }
 fn main() -> iced::Result {
+    // The state blocks on its own runtime while loading the database, so it
+    // has to be built before iced starts the tokio executor.
+    let state = State::default();
     iced::application("ChatBoto", update, view)
         .subscription(subscription)
-        .run()
+        .run_with(move || {
+            let list_models = chat_screen::list_models(&state, false);
+            (state, list_models)
+        })
 }
@@ -1,4 +1,7 @@
 use rusql_alchemy::prelude::*;
+use rusql_alchemy::PLACEHOLDER;
+
+use crate::{types::Usage, utils::provider::ModelInfo};
 
 #[derive(Model, FromRow, Clone)]
 pub struct Config {
@@ -8,3 +11,307 @@ pub struct Config {
     pub gemini_apikey: Option<String>,
     pub mistral_apikey: Option<String>,
 }
+
+/// Settings added after `Config`, as `migrate` cannot add columns to an existing table.
+#[derive(Model, FromRow, Clone, Debug)]
+pub struct Setting {
+    /// Key of the settings form, e.g. `openai.base_url`.
+    #[field(primary_key = true)]
+    pub name: String,
+    pub value: Text,
+}
+
+/// Models listed by a provider, kept until the next refresh.
+#[derive(Model, FromRow, Clone, Debug)]
+pub struct ProviderModel {
+    #[field(primary_key = true, auto = true)]
+    pub id: Integer,
+    pub provider: String,
+    pub name: String,
+    /// Tokens of its context window, unset when the listing does not tell.
+    pub context_limit: Option<Integer>,
+}
+
+/// Price of a model in USD per million tokens, edited in the settings screen.
+#[derive(Model, FromRow, Clone, Debug)]
+pub struct ModelPrice {
+    /// The `AIChoice`, e.g. `mistral/mistral-large-latest`.
+    #[field(primary_key = true)]
+    pub model: String,
+    pub input_price: Float,
+    pub output_price: Float,
+}
+
+#[derive(Model, FromRow, Clone, Debug)]
+pub struct Conversation {
+    #[field(primary_key = true, auto = true)]
+    pub id: Integer,
+    pub title: String,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+    /// `GenerationParams` as JSON, unset for the provider defaults.
+    pub params: Option<Text>,
+    /// Instructions sent before the messages.
+    pub system_prompt: Option<Text>,
+}
+
+#[derive(Model, FromRow, Clone, Debug)]
+pub struct ChatMessage {
+    #[field(primary_key = true, auto = true)]
+    pub id: Integer,
+    /// Id of the `Conversation`, not a `foreign_key` as the derive emits invalid SQL for it.
+    pub conversation_id: Integer,
+    /// `user` or `assistant`.
+    pub role: String,
+    pub content: Text,
+    /// The `AIChoice` that answered, unset for user messages.
+    pub model: Option<String>,
+    /// Why the reply ended, `stopped` when the user aborted it or a
+    /// `FinishReason` reported by the provider.
+    pub finish_reason: Option<String>,
+    /// `SafetyRating`s of the reply as JSON.
+    pub safety_ratings: Option<Text>,
+    /// `Attachment`s of the prompt as JSON.
+    pub attachments: Option<Text>,
+    pub input_tokens: Option<Integer>,
+    pub output_tokens: Option<Integer>,
+    pub created_at: DateTime,
+}
+
+/// Columns added since the first release, `migrate` only creates missing tables.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("Conversation", "params", "text"),
+    ("Conversation", "system_prompt", "text"),
+    ("ProviderModel", "context_limit", "integer"),
+    ("ChatMessage", "safety_ratings", "text"),
+    ("ChatMessage", "attachments", "text"),
+];
+
+/// Adds the missing columns to a database created by an older version.
+pub async fn upgrade(conn: &Connection) {
+    for (table, column, kind) in ADDED_COLUMNS {
+        // Fails when the column already exists.
+        sqlx::query(&format!("alter table {table} add column {column} {kind};"))
+            .execute(conn)
+            .await
+            .ok();
+    }
+}
+
+pub fn now() -> DateTime {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// `?1, ?2, ...` or `$1, $2, ...` depending on the database.
+fn placeholders(count: usize) -> String {
+    (1..=count)
+        .map(|index| format!("{}{index}", *PLACEHOLDER))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// `save` and `update` go through `kwargs!`, which strips the quotes out of text
+// values, so user and model content is written with plain binds instead.
+
+impl Setting {
+    pub async fn put(name: &str, value: &str, conn: &Connection) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "insert into Setting (name, value) values ({}) \
+             on conflict (name) do update set value = excluded.value;",
+            placeholders(2)
+        );
+        sqlx::query(&query)
+            .bind(name)
+            .bind(value)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+impl ProviderModel {
+    /// Replaces the models saved for `provider`.
+    pub async fn replace(
+        provider: &str,
+        models: &[ModelInfo],
+        conn: &Connection,
+    ) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "delete from ProviderModel where provider={}1;",
+            *PLACEHOLDER
+        );
+        sqlx::query(&query).bind(provider).execute(conn).await?;
+
+        let query = format!(
+            "insert into ProviderModel (provider, name, context_limit) values ({});",
+            placeholders(3)
+        );
+        for model in models {
+            sqlx::query(&query)
+                .bind(provider)
+                .bind(&model.name)
+                .bind(model.context_limit.map(|limit| limit as Integer))
+                .execute(conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl ModelPrice {
+    /// Replaces the whole price table.
+    pub async fn replace_all(prices: &[ModelPrice], conn: &Connection) -> Result<(), sqlx::Error> {
+        sqlx::query("delete from ModelPrice;").execute(conn).await?;
+
+        let query = format!(
+            "insert into ModelPrice (model, input_price, output_price) values ({});",
+            placeholders(3)
+        );
+        for price in prices {
+            sqlx::query(&query)
+                .bind(&price.model)
+                .bind(price.input_price)
+                .bind(price.output_price)
+                .execute(conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Cost of a reply in USD, `None` without any token count.
+    pub fn cost(&self, usage: Usage) -> Option<f64> {
+        if usage.is_empty() {
+            return None;
+        }
+        let tokens = |count: Option<u32>| count.unwrap_or_default() as f64 / 1_000_000.0;
+        Some(
+            tokens(usage.input_tokens) * self.input_price
+                + tokens(usage.output_tokens) * self.output_price,
+        )
+    }
+}
+
+impl Conversation {
+    /// Inserts the conversation with its id, which the UI picks to use it right away.
+    pub async fn insert(&self, conn: &Connection) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "insert into Conversation (id, title, created_at, updated_at, params, system_prompt) \
+             values ({});",
+            placeholders(6)
+        );
+        sqlx::query(&query)
+            .bind(self.id)
+            .bind(&self.title)
+            .bind(&self.created_at)
+            .bind(&self.updated_at)
+            .bind(&self.params)
+            .bind(&self.system_prompt)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn rename(id: Integer, title: &str, conn: &Connection) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "update Conversation set title={placeholder}1 where id={placeholder}2;",
+            placeholder = *PLACEHOLDER
+        );
+        sqlx::query(&query)
+            .bind(title)
+            .bind(id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_params(
+        id: Integer,
+        params: &str,
+        conn: &Connection,
+    ) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "update Conversation set params={placeholder}1 where id={placeholder}2;",
+            placeholder = *PLACEHOLDER
+        );
+        sqlx::query(&query)
+            .bind(params)
+            .bind(id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_system_prompt(
+        id: Integer,
+        system_prompt: &str,
+        conn: &Connection,
+    ) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "update Conversation set system_prompt={placeholder}1 where id={placeholder}2;",
+            placeholder = *PLACEHOLDER
+        );
+        sqlx::query(&query)
+            .bind(system_prompt)
+            .bind(id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Marks the conversation as active now.
+    pub async fn touch(id: Integer, conn: &Connection) -> Result<(), sqlx::Error> {
+        Conversation::set(id, kwargs!(updated_at = now()), conn).await
+    }
+
+    /// Every conversation, the most recently active first.
+    pub async fn recent(conn: &Connection) -> Result<Vec<Self>, sqlx::Error> {
+        let mut conversations = Conversation::all(conn).await?;
+        conversations.sort_by(|a, b| (&b.updated_at, b.id).cmp(&(&a.updated_at, a.id)));
+        Ok(conversations)
+    }
+
+    /// Deletes the conversation along with its messages.
+    pub async fn delete_with_messages(id: Integer, conn: &Connection) -> Result<(), sqlx::Error> {
+        for query in [
+            "delete from ChatMessage where conversation_id=",
+            "delete from Conversation where id=",
+        ] {
+            sqlx::query(&format!("{query}{}1;", *PLACEHOLDER))
+                .bind(id)
+                .execute(conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl ChatMessage {
+    pub async fn insert(&self, conn: &Connection) -> Result<(), sqlx::Error> {
+        let query = format!(
+            "insert into ChatMessage (conversation_id, role, content, model, finish_reason, \
+             safety_ratings, attachments, input_tokens, output_tokens, created_at) values ({});",
+            placeholders(10)
+        );
+        sqlx::query(&query)
+            .bind(self.conversation_id)
+            .bind(&self.role)
+            .bind(&self.content)
+            .bind(&self.model)
+            .bind(&self.finish_reason)
+            .bind(&self.safety_ratings)
+            .bind(&self.attachments)
+            .bind(self.input_tokens)
+            .bind(self.output_tokens)
+            .bind(&self.created_at)
+            .execute(conn)
+            .await?;
+        Conversation::touch(self.conversation_id, conn).await
+    }
+
+    /// Messages of a conversation, oldest first.
+    pub async fn of_conversation(id: Integer, conn: &Connection) -> Result<Vec<Self>, sqlx::Error> {
+        let mut messages = ChatMessage::filter(kwargs!(conversation_id == id), conn).await?;
+        messages.sort_by_key(|message| message.id);
+        Ok(messages)
+    }
+}
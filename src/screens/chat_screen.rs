@@ -1,36 +1,126 @@
 use iced::{
     border::Radius,
-    widget::{column, container, overlay::menu, pick_list, row, text_editor},
+    time::Duration,
+    widget::{column, container, overlay::menu, pick_list, row, text, text_editor},
     Background, Color, Element, Length, Task,
 };
 
 use rusql_alchemy::prelude::*;
+use std::time::Instant;
 
 use crate::{
-    components::{button, message_area, nav_bar, text_input::text_area},
-    models::Config,
+    components::{
+        attachments, button, markdown::Markdown, message_area, nav_bar, params_panel, sidebar,
+        text_input::text_area,
+    },
+    models::{self, ChatMessage, Config, Conversation, ProviderModel},
     services,
-    styles::{self, BLUE_SKY},
-    types::{AIMessage, Gam, MessageType, Version},
+    styles::{self, BLUE_SKY, FOOTER_GRAY},
+    types::{Attachment, Finish, GenerationParams, MessageType, Role, Turn, Usage},
+    utils::{
+        context::{self, Context},
+        provider::{self, Delta, ModelInfo, ProviderError},
+    },
     AIChoice, Message, State,
 };
 
-pub fn chat(state: &State) -> Element<Message> {
-    let choices = [
-        AIChoice::Gemini(Version::V1_5, Gam::Flash),
-        AIChoice::Gemini(Version::V1_5, Gam::Pro),
-        AIChoice::Gemini(Version::V2_0, Gam::Flash),
-        AIChoice::Gemini(Version::V2_0, Gam::Pro),
-        AIChoice::Gemini(Version::V2_5, Gam::Flash),
-        AIChoice::Gemini(Version::V2_5, Gam::Pro),
-        AIChoice::Mistral,
-    ];
-    column![
-        nav_bar::nav_bar(),
-        message_area::chat_area(state.messages.clone()),
+/// Sent by the "Continue" button of a reply cut off by the `max_tokens` limit.
+const CONTINUE_PROMPT: &str = "Continue exactly where you stopped.";
+/// Least time between two parsings of the streamed reply, parsing it on
+/// every token would take longer as the reply grows.
+const PARSE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn chat(state: &State) -> Element<'_, Message> {
+    let choices = provider::providers()
+        .iter()
+        .flat_map(|provider| {
+            let configured = provider
+                .capabilities()
+                .custom_model
+                .then(|| state.forms.get(&provider::model_key(*provider)))
+                .flatten()
+                .map(|model| model.trim())
+                .filter(|model| !model.is_empty());
+            let listed = state
+                .models
+                .get(provider.id())
+                .filter(|models| !models.is_empty());
+            let models = match listed {
+                Some(models) => models.iter().map(|model| model.name.as_str()).collect(),
+                None => provider.models().to_vec(),
+            };
+            models
+                .into_iter()
+                .chain(configured)
+                .map(|model| AIChoice::new(provider.id(), model))
+        })
+        .collect::<Vec<_>>();
+    let mut chat =
+        column![
+            nav_bar::nav_bar(),
+            message_area::chat_area(
+                state
+                    .messages
+                    .iter()
+                    .map(|(message_type, content, markdown)| {
+                        (message_type.clone(), content.as_str(), markdown)
+                    })
+                    .chain(state.pending.iter().map(
+                        |(choice, content, markdown, usage, finish)| {
+                            (
+                                MessageType::Received(choice.clone(), *usage, finish.clone()),
+                                content.as_str(),
+                                markdown,
+                            )
+                        }
+                    ))
+                    .collect(),
+                &state.prices,
+                &state.excluded,
+                state.summarized,
+            ),
+        ]
+        .spacing(10)
+        .height(Length::Fill)
+        .width(Length::Fill)
+        .padding(10);
+    if !state.excluded.is_empty() {
+        let cut = if state.summarized {
+            "summarized in"
+        } else {
+            "left out of"
+        };
+        chat = chat.push(
+            text(format!(
+                "{} earlier messages were {cut} the last request to fit the context window",
+                state.excluded.len()
+            ))
+            .size(12)
+            .color(Color::from(FOOTER_GRAY)),
+        );
+    }
+    if let Some(totals) = conversation_totals(state) {
+        chat = chat.push(
+            text(format!("This conversation: {totals}"))
+                .size(12)
+                .color(Color::from(FOOTER_GRAY)),
+        );
+    }
+    if state.params_open {
+        chat = chat.push(params_panel::params_panel(state));
+    }
+    if !state.message.is_empty() {
+        chat = chat.push(
+            text(&state.message)
+                .size(12)
+                .color(Color::from([0.0, 0.5, 0.0])),
+        );
+    }
+    chat = chat.push_maybe(attachments::attachment_bar(state));
+    chat = chat.push(
         row![
             container(text_area(&state.content)).max_height(200),
-            pick_list(choices, state.ai_choice, Message::Selected)
+            pick_list(choices, state.ai_choice.clone(), Message::Selected)
                 .style(|theme, status| {
                     pick_list::Style {
                         placeholder_color: Color::BLACK,
@@ -51,24 +141,66 @@ pub fn chat(state: &State) -> Element<Message> {
                     ..menu::default(theme)
                 })
                 .placeholder("Agents"),
-            button::rounded_button("Send", Message::Submit, |_, status| styles::primary_button(
-                status
-            )),
+            button::rounded_button("Refresh", Message::RefreshModels, |_, status| {
+                styles::primary_button(status)
+            }),
+            button::rounded_button("Parameters", Message::ToggleParams, |_, status| {
+                styles::primary_button(status)
+            }),
+            button::rounded_button("Attach", Message::PickAttachments, |_, status| {
+                styles::primary_button(status)
+            }),
+            if state.pending.is_some() {
+                button::rounded_button("Stop", Message::Stop, |_, status| {
+                    styles::danger_button(status)
+                })
+            } else {
+                button::rounded_button("Send", Message::Submit, |_, status| {
+                    styles::primary_button(status)
+                })
+            },
         ]
-        .spacing(10)
-    ]
-    .spacing(10)
-    .height(Length::Fill)
-    .width(Length::Fill)
-    .padding(10)
-    .into()
+        .spacing(10),
+    );
+
+    if state.sidebar_open {
+        row![sidebar::sidebar(state), chat].into()
+    } else {
+        chat.into()
+    }
+}
+
+/// Tokens and cost of the replies shown, `None` before any usage is known.
+fn conversation_totals(state: &State) -> Option<String> {
+    let mut totals = Usage::default();
+    let mut cost = None;
+    for (message_type, ..) in &state.messages {
+        let (MessageType::Received(choice, usage, _) | MessageType::Stopped(choice, usage)) =
+            message_type
+        else {
+            continue;
+        };
+        let add = |total: Option<u32>, tokens: Option<u32>| match (total, tokens) {
+            (None, None) => None,
+            (total, tokens) => Some(total.unwrap_or_default() + tokens.unwrap_or_default()),
+        };
+        totals = Usage {
+            input_tokens: add(totals.input_tokens, usage.input_tokens),
+            output_tokens: add(totals.output_tokens, usage.output_tokens),
+        };
+        if let Some(reply_cost) =
+            message_area::price_of(&state.prices, choice).and_then(|price| price.cost(*usage))
+        {
+            cost = Some(cost.unwrap_or_default() + reply_cost);
+        }
+    }
+    (!totals.is_empty()).then(|| message_area::usage_label(totals, cost))
 }
 
 pub fn handle_choice(state: &mut State, choice: AIChoice) -> Task<Message> {
-    state.ai_choice = Some(choice);
+    state.ai_choice = Some(choice.clone());
     let conn = state.conn.clone();
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    runtime.block_on(async {
+    Task::future(async move {
         if let Ok(Some(config)) = Config::get(kwargs!(id == 1), &conn).await {
             Config {
                 ai_choice: Some(choice.to_string()),
@@ -78,61 +210,465 @@ pub fn handle_choice(state: &mut State, choice: AIChoice) -> Task<Message> {
             .await
             .ok();
         }
-    });
-    Task::none()
+    })
+    .discard()
+}
+
+/// Asks the providers that can list their models which ones are available,
+/// only those without saved models unless `refresh` is set.
+pub fn list_models(state: &State, refresh: bool) -> Task<Message> {
+    Task::batch(
+        provider::providers()
+            .iter()
+            .filter(|provider| refresh || !state.models.contains_key(provider.id()))
+            .filter(|provider| {
+                provider
+                    .models_request(&state.credentials(**provider))
+                    .is_some()
+            })
+            .map(|provider| {
+                let id = provider.id().to_string();
+                Task::perform(
+                    services::list_models(
+                        state.client.clone(),
+                        *provider,
+                        state.credentials(*provider),
+                    ),
+                    move |models| Message::ModelsListed {
+                        provider: id.clone(),
+                        models,
+                        refresh,
+                    },
+                )
+            }),
+    )
+}
+
+/// Saves the listed models, a failed listing keeps the previous ones.
+pub fn handle_models_listed(
+    state: &mut State,
+    provider: String,
+    models: Result<Vec<ModelInfo>, ProviderError>,
+    refresh: bool,
+) -> Task<Message> {
+    let models = match models {
+        Ok(models) => models,
+        Err(err) if refresh => {
+            let name =
+                provider::get(&provider).map_or(provider.as_str(), |provider| provider.name());
+            return Task::done(Message::DisplayMessage {
+                duration: Duration::from_secs(4),
+                msg: format!("The models of {name} could not be listed: {err}"),
+            });
+        }
+        Err(_) => return Task::none(),
+    };
+    state.models.insert(provider.clone(), models.clone());
+    let conn = state.conn.clone();
+    Task::future(async move {
+        ProviderModel::replace(&provider, &models, &conn).await.ok();
+    })
+    .discard()
 }
 
 pub fn action_submit(state: &mut State) -> Task<Message> {
     let value = state.content.text();
 
-    if value.trim().is_empty() {
+    if (value.trim().is_empty() && state.attachments.is_empty()) || state.pending.is_some() {
         return Task::none();
     }
 
-    state.messages.push((MessageType::Sent, value.clone()));
+    state.content = text_editor::Content::new();
+    state.attach_error = None;
+    let attachments = std::mem::take(&mut state.attachments);
+    send_prompt(state, value, attachments)
+}
 
-    let api_key = match state.ai_choice {
-        Some(AIChoice::Gemini(_, _)) => state.forms.get("gemini").cloned().unwrap_or_default(),
-        Some(AIChoice::Mistral) => state.forms.get("mistral").cloned().unwrap_or_default(),
-        None => "".to_string(),
+/// Asks for the rest of a reply cut off by the `max_tokens` limit.
+pub fn action_continue(state: &mut State) -> Task<Message> {
+    if state.pending.is_some()
+        || !state
+            .messages
+            .last()
+            .is_some_and(|(message_type, ..)| message_type.cut_off())
+    {
+        return Task::none();
+    }
+    send_prompt(state, CONTINUE_PROMPT.to_string(), Vec::new())
+}
+
+fn send_prompt(state: &mut State, value: String, attachments: Vec<Attachment>) -> Task<Message> {
+    state.push_message(MessageType::Sent(attachments.clone()), value.clone());
+    let save = save_message(
+        state,
+        ChatMessage {
+            role: "user".to_string(),
+            content: value.clone(),
+            attachments: (!attachments.is_empty())
+                .then(|| serde_json::to_string(&attachments).ok())
+                .flatten(),
+            created_at: models::now(),
+            ..Default::default()
+        },
+    );
+    let prompt = Turn {
+        role: Role::User,
+        content: value,
+        attachments,
     };
-    let choice = state.ai_choice.unwrap_or_default();
-    let history = match choice {
-        AIChoice::Gemini(_, _) => state.gemini_history.clone(),
-        AIChoice::Mistral => state.mistral_history.clone(),
+    Task::batch([save, request_reply(state, prompt)])
+}
+
+/// Sends the last prompt again after a failure, possibly to another model.
+pub fn action_retry(state: &mut State) -> Task<Message> {
+    if state.pending.is_some() || !matches!(state.messages.last(), Some((MessageType::Error, ..))) {
+        return Task::none();
+    }
+    state.messages.pop();
+
+    let prompt = state
+        .messages
+        .iter()
+        .rev()
+        .find_map(|(message_type, content, _)| match message_type {
+            MessageType::Sent(attachments) => Some(Turn {
+                role: Role::User,
+                content: content.clone(),
+                attachments: attachments.clone(),
+            }),
+            _ => None,
+        });
+    match prompt {
+        Some(prompt) => request_reply(state, prompt),
+        None => Task::none(),
+    }
+}
+
+fn request_reply(state: &mut State, prompt: Turn) -> Task<Message> {
+    let choice = state.ai_choice.clone().unwrap_or_default();
+    let Some(provider) = provider::get(&choice.provider) else {
+        return Task::none();
     };
-    let task = Task::perform(services::ask_ai(choice, value, history, api_key), |resp| {
-        Message::AIRespond(resp.unwrap_or_else(|err| err.to_string()))
+
+    state.pending = Some((
+        choice.clone(),
+        String::new(),
+        Markdown::default(),
+        Usage::default(),
+        Finish::default(),
+    ));
+    state.pending_parsed = None;
+    state.transcript.push(prompt);
+
+    let (strategy, last_turns) = state.context_strategy();
+    let context = context::fit(
+        &state.system_prompt,
+        state.transcript.clone(),
+        state.context_limit(provider, &choice.model),
+        state.params.max_tokens,
+        strategy,
+        last_turns,
+    );
+    state.excluded = state
+        .transcript_messages()
+        .into_iter()
+        .take(context.excluded)
+        .collect();
+    state.summarized = !context.earlier.is_empty();
+    if context.earlier.is_empty() {
+        let system = state.system_prompt.clone();
+        return ask(state, choice, system, context.transcript, Usage::default());
+    }
+
+    // The summary kept from the last requests only needs the turns left out since.
+    let cached = state
+        .summary
+        .as_ref()
+        .filter(|(covered, _)| *covered <= context.excluded);
+    if let Some((_, summary)) = cached.filter(|(covered, _)| *covered == context.excluded) {
+        let system = context::with_summary(&state.system_prompt, summary);
+        return ask(state, choice, system, context.transcript, Usage::default());
+    }
+    let (covered, previous) = cached.map_or((0, None), |(covered, summary)| {
+        (*covered, Some(summary.clone()))
     });
+    let earlier = context.earlier[covered..].to_vec();
 
-    state.content = text_editor::Content::new();
+    let summarize = services::summarize(
+        state.client.clone(),
+        choice.clone(),
+        previous,
+        earlier,
+        state.context_limit(provider, &choice.model),
+        state.credentials(provider),
+    );
+    let (task, handle) = Task::future(async move {
+        Message::Summarized {
+            summary: summarize.await,
+            context,
+        }
+    })
+    .abortable();
+    state.generation = Some(handle);
     task
 }
 
-pub fn handle_ai_response(state: &mut State, response: String) -> Task<Message> {
-    match state.ai_choice {
-        Some(AIChoice::Gemini(_, _)) => {
-            state.messages.push((
-                MessageType::Received(AIChoice::Gemini(Version::default(), Gam::default())),
-                response.clone(),
-            ));
+/// Keeps the summary of the turns left out and asks for the reply with it.
+pub fn handle_summarized(
+    state: &mut State,
+    context: Context,
+    summary: Result<(String, Usage), ProviderError>,
+) -> Task<Message> {
+    state.generation = None;
+    let Some((choice, ..)) = state.pending.as_ref() else {
+        return Task::none();
+    };
+    let choice = choice.clone();
+    match summary {
+        Ok((summary, usage)) => {
+            let system = context::with_summary(&state.system_prompt, &summary);
+            state.summary = Some((context.excluded, summary));
+            ask(state, choice, system, context.transcript, usage)
+        }
+        Err(err) => handle_ai_failure(state, err),
+    }
+}
 
-            state.gemini_history.push(AIMessage {
-                role: "model".to_string(),
-                content: response,
-            });
+fn ask(
+    state: &mut State,
+    choice: AIChoice,
+    system: String,
+    transcript: Vec<Turn>,
+    spent: Usage,
+) -> Task<Message> {
+    let Some(provider) = provider::get(&choice.provider) else {
+        return Task::none();
+    };
+    let credentials = state.credentials(provider);
+    let (task, handle) = Task::run(
+        services::ask_ai(
+            state.client.clone(),
+            choice,
+            system,
+            transcript,
+            credentials,
+            state.params.clone(),
+            spent,
+        ),
+        |delta| match delta {
+            Ok(delta) => Message::AIDelta(delta),
+            Err(err) => Message::AIFailed(err),
+        },
+    )
+    .chain(Task::done(Message::AIDone))
+    .abortable();
+    state.generation = Some(handle);
+    task
+}
+
+pub fn action_stop(state: &mut State) -> Task<Message> {
+    if let Some(handle) = state.generation.take() {
+        handle.abort();
+    }
+    let Some((choice, response, _, usage, finish)) = state.pending.take() else {
+        return Task::none();
+    };
+    commit_reply(state, choice, response, usage, finish, true)
+}
+
+pub fn handle_ai_delta(state: &mut State, delta: Delta) -> Task<Message> {
+    if let Some((_, content, markdown, usage, finish)) = state.pending.as_mut() {
+        if !delta.text.is_empty() {
+            content.push_str(&delta.text);
+            // The complete reply is parsed once committed.
+            if state
+                .pending_parsed
+                .is_none_or(|parsed| parsed.elapsed() >= PARSE_INTERVAL)
+            {
+                *markdown = Markdown::parse(content);
+                state.pending_parsed = Some(Instant::now());
+            }
         }
-        Some(AIChoice::Mistral) => {
-            state
-                .messages
-                .push((MessageType::Received(AIChoice::Mistral), response.clone()));
-
-            state.mistral_history.push(AIMessage {
-                role: "assistant".to_string(),
-                content: response,
-            });
+        *usage = usage.merge(delta.usage);
+        if let Some(delta_finish) = delta.finish {
+            *finish = finish.clone().merge(delta_finish);
+        }
+    }
+    Task::none()
+}
+
+/// Replaces the pending reply with an error bubble, the unanswered prompt
+/// leaves the transcript until it is retried.
+pub fn handle_ai_failure(state: &mut State, err: ProviderError) -> Task<Message> {
+    if state.pending.take().is_some() {
+        state.pop_unanswered_prompt();
+        state.push_message(MessageType::Error, err.to_string());
+    }
+    Task::none()
+}
+
+pub fn handle_ai_done(state: &mut State) -> Task<Message> {
+    state.generation = None;
+    let Some((choice, response, _, usage, finish)) = state.pending.take() else {
+        return Task::none();
+    };
+    commit_reply(state, choice, response, usage, finish, false)
+}
+
+fn commit_reply(
+    state: &mut State,
+    choice: AIChoice,
+    response: String,
+    usage: Usage,
+    finish: Finish,
+    stopped: bool,
+) -> Task<Message> {
+    let finish_reason = if stopped {
+        Some("stopped".to_string())
+    } else {
+        finish
+            .reason
+            .as_ref()
+            .map(|reason| reason.as_str().to_string())
+    };
+    let message = ChatMessage {
+        role: "assistant".to_string(),
+        content: response.clone(),
+        model: Some(choice.to_string()),
+        finish_reason,
+        safety_ratings: (!finish.safety_ratings.is_empty())
+            .then(|| serde_json::to_string(&finish.safety_ratings).ok())
+            .flatten(),
+        input_tokens: usage.input_tokens.map(|tokens| tokens as Integer),
+        output_tokens: usage.output_tokens.map(|tokens| tokens as Integer),
+        created_at: models::now(),
+        ..Default::default()
+    };
+    let message_type = if stopped {
+        MessageType::Stopped(choice, usage)
+    } else {
+        MessageType::Received(choice, usage, finish)
+    };
+    state.push_message(message_type, response.clone());
+
+    // Providers reject empty turns, a reply stopped before any text is dropped.
+    if response.is_empty() {
+        state.pop_unanswered_prompt();
+    } else {
+        state.transcript.push(Turn::new(Role::Assistant, response));
+    }
+    save_message(state, message)
+}
+
+/// Saves a message of the shown conversation, which is created with the first one.
+fn save_message(state: &mut State, mut message: ChatMessage) -> Task<Message> {
+    let created = match state.conversation {
+        Some(id) => {
+            if let Some(index) = state.conversations.iter().position(|c| c.id == id) {
+                let mut conversation = state.conversations.remove(index);
+                conversation.updated_at = models::now();
+                state.conversations.insert(0, conversation);
+            }
+            None
+        }
+        None => {
+            let conversation = Conversation {
+                id: state.next_conversation,
+                title: title_of(&message.content),
+                created_at: models::now(),
+                updated_at: models::now(),
+                params: serde_json::to_string(&state.params).ok(),
+                system_prompt: Some(state.system_prompt.clone()),
+            };
+            state.next_conversation += 1;
+            state.conversation = Some(conversation.id);
+            state.conversations.insert(0, conversation.clone());
+            Some(conversation)
+        }
+    };
+    message.conversation_id = state.conversation.unwrap_or_default();
+
+    let conn = state.conn.clone();
+    Task::future(async move {
+        if let Some(conversation) = created {
+            conversation.insert(&conn).await.ok();
         }
-        None => (),
+        message.insert(&conn).await.ok();
+    })
+    .discard()
+}
+
+/// Shows an empty chat, saved once its first message is sent.
+pub fn action_new_conversation(state: &mut State) -> Task<Message> {
+    let stop = action_stop(state);
+    state.conversation = None;
+    state.messages.clear();
+    state.transcript.clear();
+    state.summary = None;
+    state.excluded.clear();
+    state.system_prompt.clear();
+    state.set_params(GenerationParams::default());
+    stop
+}
+
+pub fn action_open_conversation(state: &mut State, id: Integer) -> Task<Message> {
+    if state.conversation == Some(id) {
+        return Task::none();
+    }
+    let stop = action_new_conversation(state);
+    state.conversation = Some(id);
+    let conn = state.conn.clone();
+    let load = Task::perform(
+        async move { ChatMessage::of_conversation(id, &conn).await },
+        move |messages| Message::ConversationLoaded(id, messages.unwrap_or_default()),
+    );
+    Task::batch([stop, load])
+}
+
+pub fn handle_conversation_loaded(
+    state: &mut State,
+    id: Integer,
+    messages: Vec<ChatMessage>,
+) -> Task<Message> {
+    // Another conversation may have been opened while this one was loading.
+    if state.conversation == Some(id) {
+        state.open_conversation(id, messages);
     }
     Task::none()
 }
+
+/// Deletes the conversation whose deletion was confirmed from the sidebar.
+pub fn action_delete_conversation(state: &mut State) -> Task<Message> {
+    let Some(id) = state.confirm_delete.take() else {
+        return Task::none();
+    };
+    state
+        .conversations
+        .retain(|conversation| conversation.id != id);
+    let new = if state.conversation == Some(id) {
+        // Stopping would save the partial reply to the deleted conversation.
+        if let Some(handle) = state.generation.take() {
+            handle.abort();
+        }
+        state.pending = None;
+        action_new_conversation(state)
+    } else {
+        Task::none()
+    };
+
+    let conn = state.conn.clone();
+    let delete = Task::future(async move {
+        Conversation::delete_with_messages(id, &conn).await.ok();
+    })
+    .discard();
+    Task::batch([new, delete])
+}
+
+fn title_of(prompt: &str) -> String {
+    const MAX_TITLE_LENGTH: usize = 40;
+    let line = prompt.trim().lines().next().unwrap_or("Attachment");
+    match line.char_indices().nth(MAX_TITLE_LENGTH) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
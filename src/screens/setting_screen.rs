@@ -1,47 +1,67 @@
 use iced::{
     time::Duration,
-    widget::{column, row, text},
+    widget::{column, pick_list, row, scrollable, text, text_input, Row},
     Color, Element, Length, Task,
 };
 use rusql_alchemy::prelude::*;
 
 use crate::{
     components::{button::rounded_button, input_form::input_form},
-    styles, Config, Message, Screen, State,
+    models::{ModelPrice, Setting},
+    screens::chat_screen,
+    state, styles,
+    types::ContextStrategy,
+    utils::provider::{self, Provider},
+    Config, Message, Screen, State,
 };
 
-pub fn setting(state: &State) -> Element<Message> {
+/// Listed first among the safety thresholds, leaves the category unset.
+const DEFAULT_THRESHOLD: &str = "default";
+
+pub fn setting(state: &State) -> Element<'_, Message> {
+    let providers = column(provider::providers().iter().map(|provider| {
+        let mut fields =
+            column![text(provider.name()).center().size(13).color(Color::BLACK)].spacing(5);
+        let capabilities = provider.capabilities();
+        if capabilities.base_url {
+            fields = fields.push(setting_field(
+                state,
+                "Base URL",
+                provider::base_url_key(*provider),
+                false,
+            ));
+        }
+        if capabilities.custom_model {
+            fields = fields.push(setting_field(
+                state,
+                "Model name",
+                provider::model_key(*provider),
+                false,
+            ));
+        }
+        if capabilities.api_key {
+            fields = fields.push(setting_field(
+                state,
+                "API key",
+                provider.id().to_string(),
+                true,
+            ));
+        }
+        for (category, label) in provider.safety_categories() {
+            fields = fields.push(safety_field(state, *provider, category, label));
+        }
+        fields.into()
+    }))
+    .spacing(10);
+
     column![
         text("Setting")
             .size(20)
             .color(Color::BLACK)
             .center()
             .width(Length::Fill),
-        text("Mistral API key")
-            .center()
-            .size(13)
-            .color(Color::BLACK),
-        input_form(
-            "Add your mistral api key here",
-            "mistral",
-            &state.forms,
-            |value| Message::InputForm {
-                key: "mistral".to_string(),
-                value,
-            },
-            true
-        ),
-        text("Gemini API key").center().size(13).color(Color::BLACK),
-        input_form(
-            "Add your gemini api key here",
-            "gemini",
-            &state.forms,
-            |value| Message::InputForm {
-                key: "gemini".to_string(),
-                value
-            },
-            true
-        ),
+        scrollable(column![providers, context(state), prices(state)].spacing(20))
+            .height(Length::Fill),
         row![
             rounded_button("Cancel", Message::Route(Screen::ChatScreen), |_, status| {
                 styles::danger_button(status)
@@ -62,25 +82,226 @@ pub fn setting(state: &State) -> Element<Message> {
     .into()
 }
 
+/// Threshold from which the replies are blocked for `category`, unset for the provider default.
+fn safety_field<'l>(
+    state: &'l State,
+    provider: &'static dyn Provider,
+    category: &str,
+    label: &'l str,
+) -> Element<'l, Message> {
+    let key = provider::safety_key(provider, category);
+    let thresholds = [DEFAULT_THRESHOLD]
+        .into_iter()
+        .chain(provider.safety_thresholds().iter().copied())
+        .collect::<Vec<_>>();
+    let selected = state
+        .forms
+        .get(&key)
+        .map(String::as_str)
+        .filter(|threshold| !threshold.is_empty())
+        .unwrap_or(DEFAULT_THRESHOLD);
+    row![
+        text(label).width(Length::Fill),
+        pick_list(thresholds, Some(selected), move |threshold| {
+            Message::InputForm {
+                key: key.clone(),
+                value: match threshold {
+                    DEFAULT_THRESHOLD => String::new(),
+                    threshold => threshold.to_string(),
+                },
+            }
+        }),
+    ]
+    .spacing(5)
+    .into()
+}
+
+fn context(state: &State) -> Element<'_, Message> {
+    let (selected, _) = state.context_strategy();
+    let strategies = Row::with_children(ContextStrategy::ALL.map(|strategy| {
+        rounded_button(
+            strategy.label(),
+            Message::InputForm {
+                key: state::CONTEXT_STRATEGY_KEY.to_string(),
+                value: strategy.label().to_string(),
+            },
+            move |_, status| {
+                if strategy == selected {
+                    styles::primary_button(status)
+                } else {
+                    styles::secondary_button(status)
+                }
+            },
+        )
+    }))
+    .spacing(5);
+
+    column![
+        text("When a conversation exceeds the context window of the model")
+            .center()
+            .size(13)
+            .color(Color::BLACK),
+        strategies,
+        setting_field(
+            state,
+            "Turns kept by \"last turns\", 10 by default",
+            state::LAST_TURNS_KEY.to_string(),
+            false,
+        ),
+    ]
+    .spacing(5)
+    .into()
+}
+
+fn prices(state: &State) -> Element<'_, Message> {
+    let rows = column(state.price_inputs.iter().enumerate().map(
+        |(index, (model, input_price, output_price))| {
+            row![
+                text(model).width(Length::Fill),
+                text_input("Input", input_price)
+                    .style(styles::rounded_input)
+                    .on_input(move |value| Message::InputPrice {
+                        index,
+                        output: false,
+                        value,
+                    })
+                    .width(100),
+                text_input("Output", output_price)
+                    .style(styles::rounded_input)
+                    .on_input(move |value| Message::InputPrice {
+                        index,
+                        output: true,
+                        value,
+                    })
+                    .width(100),
+                rounded_button("Remove", Message::RemovePrice(index), |_, status| {
+                    styles::danger_button(status)
+                }),
+            ]
+            .spacing(5)
+            .into()
+        },
+    ))
+    .spacing(5);
+
+    column![
+        text("Prices in USD per million tokens, input and output")
+            .center()
+            .size(13)
+            .color(Color::BLACK),
+        rows,
+        rounded_button("Add the selected model", Message::AddPrice, |_, status| {
+            styles::primary_button(status)
+        }),
+    ]
+    .spacing(5)
+    .into()
+}
+
+pub fn handle_price_input(
+    state: &mut State,
+    index: usize,
+    output: bool,
+    value: String,
+) -> Task<Message> {
+    if let Some((_, input_price, output_price)) = state.price_inputs.get_mut(index) {
+        if output {
+            *output_price = value;
+        } else {
+            *input_price = value;
+        }
+    }
+    Task::none()
+}
+
+pub fn add_price(state: &mut State) -> Task<Message> {
+    let model = state.ai_choice.clone().unwrap_or_default().to_string();
+    if !state
+        .price_inputs
+        .iter()
+        .any(|(priced, ..)| *priced == model)
+    {
+        state
+            .price_inputs
+            .push((model, String::new(), String::new()));
+    }
+    Task::none()
+}
+
+pub fn remove_price(state: &mut State, index: usize) -> Task<Message> {
+    if index < state.price_inputs.len() {
+        state.price_inputs.remove(index);
+    }
+    Task::none()
+}
+
+fn setting_field<'l>(
+    state: &'l State,
+    placeholder: &str,
+    key: String,
+    is_secure: bool,
+) -> Element<'l, Message> {
+    let field = key.clone();
+    input_form(
+        placeholder,
+        &field,
+        &state.forms,
+        move |value| Message::InputForm {
+            key: key.clone(),
+            value,
+        },
+        is_secure,
+    )
+}
+
 pub fn save_setting(state: &mut State) -> Task<Message> {
     let mistral_apikey = state.forms.get("mistral").cloned();
     let gemini_apikey = state.forms.get("gemini").cloned();
-    let conn = &state.conn;
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    runtime.block_on(async {
-        if let Ok(Some(config)) = Config::get(kwargs!(id == 1), conn).await {
-            Config {
-                gemini_apikey,
-                mistral_apikey,
-                ..config
+    // Fields added after the `Config` columns live in the `Setting` table.
+    let settings = state
+        .forms
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "mistral" | "gemini"))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect::<Vec<_>>();
+    // Rows with an invalid price are left out.
+    state.prices = state
+        .price_inputs
+        .iter()
+        .filter_map(|(model, input_price, output_price)| {
+            Some(ModelPrice {
+                model: model.clone(),
+                input_price: input_price.trim().parse().ok()?,
+                output_price: output_price.trim().parse().ok()?,
+            })
+        })
+        .collect();
+    state.price_inputs = state::price_inputs_of(&state.prices);
+    let prices = state.prices.clone();
+    let conn = state.conn.clone();
+    // A new host or key can change the models available.
+    let list_models = chat_screen::list_models(state, true);
+    let save = Task::perform(
+        async move {
+            if let Ok(Some(config)) = Config::get(kwargs!(id == 1), &conn).await {
+                Config {
+                    gemini_apikey,
+                    mistral_apikey,
+                    ..config
+                }
+                .update(&conn)
+                .await
+                .ok();
             }
-            .update(conn)
-            .await
-            .ok();
-        }
-    });
-    Task::done(Message::DisplayMessage {
-        duration: Duration::from_secs(2),
-        msg: "Sucesss".to_string(),
-    })
+            for (name, value) in settings {
+                Setting::put(&name, &value, &conn).await.ok();
+            }
+            ModelPrice::replace_all(&prices, &conn).await.ok();
+        },
+        |_| Message::DisplayMessage {
+            duration: Duration::from_secs(2),
+            msg: "Sucesss".to_string(),
+        },
+    );
+    Task::batch([save, list_models])
 }
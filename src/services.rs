@@ -1,14 +1,290 @@
-use crate::{types::AIMessage, utils::gemini::ask_gemini, utils::mistral::ask_mistral, AIChoice};
-use anyhow::Result;
+use crate::{
+    types::{Attachment, GenerationParams, Turn, Usage},
+    utils::context,
+    utils::fetch::{fetch, fetch_get, fetch_stream, FetchError},
+    utils::pdf,
+    utils::provider::{self, Credentials, Delta, ModelInfo, Provider, ProviderError},
+    AIChoice,
+};
+use base64::prelude::*;
+use iced::futures::{SinkExt, Stream, StreamExt};
+use reqwest::Client;
+use serde_json::Value;
+use std::path::PathBuf;
 
-pub async fn ask_ai(
+/// Asks the provider of `choice` to answer the last turn of `transcript`,
+/// following the `system` instructions, and yields the reply as it arrives.
+///
+/// The tokens `spent` before the reply, on summarizing, are counted with its own.
+/// Providers without streaming support yield their whole reply at once.
+/// Dropping the stream cancels the underlying request.
+pub fn ask_ai(
+    client: Client,
     choice: AIChoice,
-    text: String,
-    history: Vec<AIMessage>,
-    api_key: String,
-) -> Result<String> {
-    match choice {
-        AIChoice::Gemini(v, g) => ask_gemini(v, g, text, history, api_key).await,
-        AIChoice::Mistral => ask_mistral(text, history, api_key).await,
+    system: String,
+    transcript: Vec<Turn>,
+    credentials: Credentials,
+    params: GenerationParams,
+    spent: Usage,
+) -> impl Stream<Item = Result<Delta, ProviderError>> {
+    iced::stream::try_channel(16, move |mut output| async move {
+        let provider = get_provider(&choice)?;
+        let transcript = transcript
+            .into_iter()
+            .map(context::inline_files)
+            .collect::<Vec<_>>();
+        if !spent.is_empty()
+            && output
+                .send(Delta {
+                    usage: spent,
+                    ..Default::default()
+                })
+                .await
+                .is_err()
+        {
+            return Ok(());
+        }
+        let add_spent = |mut delta: Delta| {
+            delta.usage = delta.usage.add(spent);
+            delta
+        };
+
+        let stream = provider.capabilities().streaming;
+        let request = provider.build_request(
+            &choice.model,
+            &system,
+            &transcript,
+            &credentials,
+            &params,
+            stream,
+        )?;
+
+        if stream {
+            let events = fetch_stream(
+                &client,
+                &request.url,
+                request.body,
+                request.headers,
+                provider.stream_format(),
+            )
+            .await
+            .map_err(|err| provider_error(provider, err))?;
+            let mut events = std::pin::pin!(events);
+            while let Some(event) = events.next().await {
+                let event = event.map_err(|err| provider_error(provider, err))?;
+                if let Some(delta) = provider.parse_stream_event(&event)? {
+                    if output.send(add_spent(delta)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            let response: Value = fetch(&client, &request.url, request.body, request.headers)
+                .await
+                .map_err(|err| provider_error(provider, err))?;
+            output
+                .send(add_spent(provider.parse_response(response)?))
+                .await
+                .ok();
+        }
+        Ok(())
+    })
+}
+
+/// Summarizes the `earlier` turns with the model of `choice`, extending the
+/// `previous` summary of the turns before them, and counts the tokens spent.
+///
+/// Turns that do not fit in the `limit` tokens of the model in one request are
+/// summarized over several, each extending the summary of the last.
+pub async fn summarize(
+    client: Client,
+    choice: AIChoice,
+    previous: Option<String>,
+    earlier: Vec<Turn>,
+    limit: u32,
+    credentials: Credentials,
+) -> Result<(String, Usage), ProviderError> {
+    let provider = get_provider(&choice)?;
+    let params = GenerationParams {
+        max_tokens: Some(context::SUMMARY_TOKENS),
+        ..Default::default()
+    };
+    let mut summary = previous;
+    let mut usage = Usage {
+        input_tokens: Some(0),
+        output_tokens: Some(0),
+    };
+    for chunk in context::summary_chunks(&earlier, limit) {
+        let request = provider.build_request(
+            &choice.model,
+            "",
+            &context::summary_request(summary.as_deref(), &chunk),
+            &credentials,
+            &params,
+            false,
+        )?;
+        let response: Value = fetch(&client, &request.url, request.body, request.headers)
+            .await
+            .map_err(|err| provider_error(provider, err))?;
+        let reply = provider.parse_response(response)?;
+        usage = usage.add(reply.usage);
+        summary = Some(reply.text);
+    }
+    Ok((summary.unwrap_or_default(), usage))
+}
+
+fn get_provider(choice: &AIChoice) -> Result<&'static dyn Provider, ProviderError> {
+    provider::get(&choice.provider)
+        .ok_or_else(|| ProviderError::BadRequest(format!("unknown provider '{}'", choice.provider)))
+}
+
+/// Lists the models of a provider that discovers them, none for the others.
+pub async fn list_models(
+    client: Client,
+    provider: &dyn Provider,
+    credentials: Credentials,
+) -> Result<Vec<ModelInfo>, ProviderError> {
+    let Some(request) = provider.models_request(&credentials) else {
+        return Ok(Vec::new());
+    };
+    let response: Value = fetch_get(&client, &request.url, request.headers)
+        .await
+        .map_err(|err| provider_error(provider, err))?;
+    provider.parse_models(response)
+}
+
+/// Reads a file to attach to the next prompt: a PNG or JPEG image, a PDF
+/// whose text is extracted, or any text file.
+///
+/// Attachments are sent again with every later request, so their size is
+/// limited below what the providers accept.
+pub async fn read_attachment(path: PathBuf) -> Result<Attachment, String> {
+    const MAX_TEXT_SIZE: usize = 1_048_576;
+    /// Anthropic takes images up to 5 MB once in base64, a third larger.
+    const MAX_IMAGE_SIZE: usize = 3 * 1_048_576;
+    const MAX_PDF_SIZE: usize = 10 * 1_048_576;
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let size = tokio::fs::metadata(&path)
+        .await
+        .map_err(|err| format!("{name}: {err}"))?
+        .len() as usize;
+    match extension.as_deref() {
+        Some("png" | "jpg" | "jpeg") if size > MAX_IMAGE_SIZE => {
+            return Err(format!("{name}: images are limited to 3 MB"));
+        }
+        Some("pdf") if size > MAX_PDF_SIZE => {
+            return Err(format!("{name}: PDFs are limited to 10 MB"));
+        }
+        Some("png" | "jpg" | "jpeg" | "pdf") => {}
+        _ if size > MAX_TEXT_SIZE => {
+            return Err(format!("{name}: text files are limited to 1 MB"));
+        }
+        _ => {}
+    }
+
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|err| format!("{name}: {err}"))?;
+    let size = bytes.len();
+    let (mime_type, data) = match extension.as_deref() {
+        Some("png") => ("image/png", BASE64_STANDARD.encode(bytes)),
+        Some("jpg" | "jpeg") => ("image/jpeg", BASE64_STANDARD.encode(bytes)),
+        Some("pdf") => {
+            // Parsing a large PDF would hold up the other tasks.
+            let text = tokio::task::spawn_blocking(move || pdf::extract_text(&bytes))
+                .await
+                .map_err(|err| format!("{name}: {err}"))?
+                .map_err(|err| format!("{name}: {err}"))?;
+            if text.len() > MAX_TEXT_SIZE {
+                return Err(format!("{name}: the text of PDFs is limited to 1 MB"));
+            }
+            ("application/pdf", text)
+        }
+        _ => {
+            let text = String::from_utf8(bytes)
+                .map_err(|_| format!("{name}: not an image, a PDF or a text file"))?;
+            ("text/plain", text)
+        }
+    };
+    Ok(Attachment::new(
+        name,
+        mime_type.to_string(),
+        data.into(),
+        size,
+    ))
+}
+
+/// Command opening a URL with the default application of the system.
+#[cfg(target_os = "macos")]
+const OPENER: &[&str] = &["open"];
+#[cfg(target_os = "windows")]
+const OPENER: &[&str] = &["rundll32", "url.dll,FileProtocolHandler"];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const OPENER: &[&str] = &["xdg-open"];
+
+/// Opens a web or mail link from a reply in the system browser. Other schemes
+/// could run local files, so they are refused.
+pub async fn open_link(url: String) -> Result<(), String> {
+    let scheme = url.split_once(':').map(|(scheme, _)| scheme.to_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https" | "mailto")) {
+        return Err(format!("Only web and mail links can be opened: {url}"));
+    }
+    let status = tokio::process::Command::new(OPENER[0])
+        .args(&OPENER[1..])
+        .arg(&url)
+        .status()
+        .await
+        .map_err(|err| format!("Could not open {url}: {err}"))?;
+    if !status.success() {
+        return Err(format!("Could not open {url}: {status}"));
+    }
+    Ok(())
+}
+
+fn provider_error(provider: &dyn Provider, err: FetchError) -> ProviderError {
+    match err {
+        FetchError::Status { status, body } => provider.parse_error(status, &body),
+        FetchError::Decode(err) => ProviderError::Decode(err.to_string()),
+        FetchError::Timeout | FetchError::Network(_) => ProviderError::Network(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(name: &str, size: usize) -> Result<Attachment, String> {
+        let dir = std::env::temp_dir().join(format!("chatboto-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join(name);
+        tokio::fs::write(&path, vec![b'a'; size]).await.unwrap();
+        let attachment = read_attachment(path.clone()).await;
+        tokio::fs::remove_file(&path).await.ok();
+        attachment
+    }
+
+    #[tokio::test]
+    async fn limits_the_size_of_attachments() {
+        let error = read("photo.png", 4 * 1_048_576).await.unwrap_err();
+        assert_eq!(error, "photo.png: images are limited to 3 MB");
+        let error = read("report.pdf", 11 * 1_048_576).await.unwrap_err();
+        assert_eq!(error, "report.pdf: PDFs are limited to 10 MB");
+        let error = read("notes.txt", 1_048_577).await.unwrap_err();
+        assert_eq!(error, "notes.txt: text files are limited to 1 MB");
+    }
+
+    #[tokio::test]
+    async fn reads_a_text_file() {
+        let attachment = read("short.txt", 10).await.unwrap();
+        assert_eq!(attachment.mime_type, "text/plain");
+        assert_eq!(&*attachment.data, "aaaaaaaaaa");
+        assert_eq!(attachment.size, 10);
     }
 }
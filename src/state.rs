@@ -1,21 +1,80 @@
+use iced::task;
 use iced::time::Duration;
 use iced::widget::text_editor;
 use rusql_alchemy::prelude::*;
+use std::{collections::HashMap, time::Instant};
 
 use crate::{
-    models::Config,
-    types::{AIChoice, AIMessage, FormState, Gam, MessageType, Screen, Version},
+    components::{markdown::Markdown, params_panel},
+    models::{self, ChatMessage, Config, Conversation, ModelPrice, ProviderModel, Setting},
+    types::{
+        AIChoice, Attachment, ContextStrategy, Finish, FinishReason, FormState, GenerationParams,
+        MessageType, Role, Screen, Turn, Usage,
+    },
+    utils::{
+        fetch,
+        provider::{self, Credentials, ModelInfo, Provider},
+    },
 };
 
+pub const CONTEXT_STRATEGY_KEY: &str = "context.strategy";
+pub const LAST_TURNS_KEY: &str = "context.last_turns";
+const DEFAULT_LAST_TURNS: usize = 10;
+
 pub struct State {
-    pub messages: Vec<(MessageType, String)>,
+    /// Shown messages with their text parsed once for the view.
+    pub messages: Vec<(MessageType, String, Markdown)>,
     pub ai_choice: Option<AIChoice>,
-    pub gemini_history: Vec<AIMessage>,
-    pub mistral_history: Vec<AIMessage>,
+    /// Models listed by the providers, by provider id, saved until refreshed.
+    pub models: HashMap<String, Vec<ModelInfo>>,
+    /// Reply being streamed, committed to `messages` once complete.
+    pub pending: Option<(AIChoice, String, Markdown, Usage, Finish)>,
+    /// When the text of `pending` was last parsed for the view.
+    pub pending_parsed: Option<Instant>,
+    /// Aborts the request streaming `pending`.
+    pub generation: Option<task::Handle>,
+    /// Context sent with every request, shared by all providers.
+    pub transcript: Vec<Turn>,
+    /// Indices in `messages` of those left out of the last request to fit the context window.
+    pub excluded: Vec<usize>,
+    /// Whether the excluded messages were summarized rather than dropped.
+    pub summarized: bool,
+    /// Summary of the first turns of `transcript` with their count, extended
+    /// as more turns are left out.
+    pub summary: Option<(usize, String)>,
+    /// Saved conversation shown, created when its first message is sent.
+    pub conversation: Option<Integer>,
+    /// Saved conversations, the most recently active first.
+    pub conversations: Vec<Conversation>,
+    /// Id given to the next conversation, known before it is saved.
+    pub next_conversation: Integer,
+    pub sidebar_open: bool,
+    /// Conversation being renamed with the title typed so far.
+    pub renaming: Option<(Integer, String)>,
+    /// Conversation waiting for its deletion to be confirmed.
+    pub confirm_delete: Option<Integer>,
+    /// Prices used to cost the replies, edited in the settings screen.
+    pub prices: Vec<ModelPrice>,
+    /// Text of the price table rows: model, input price and output price.
+    pub price_inputs: Vec<(String, String, String)>,
+    /// Instructions of the shown conversation, empty for none.
+    pub system_prompt: String,
+    /// Sampling of the shown conversation.
+    pub params: GenerationParams,
+    /// Text of the parameter panel fields.
+    pub param_inputs: FormState,
+    pub params_open: bool,
+    /// Counts the edits of each conversation's settings, only the last one is saved.
+    pub conversation_edits: HashMap<Integer, u64>,
     pub content: text_editor::Content,
+    /// Files sent with the next prompt.
+    pub attachments: Vec<Attachment>,
+    /// Why the last file could not be attached.
+    pub attach_error: Option<String>,
     pub screen: Screen,
     pub forms: FormState,
     pub conn: Connection,
+    pub client: reqwest::Client,
 
     pub tick: Duration,
     pub timer_enabled: bool,
@@ -27,20 +86,42 @@ impl Default for State {
     fn default() -> Self {
         let runtime = tokio::runtime::Runtime::new().unwrap();
 
-        let (conn, config) = runtime.block_on(async {
-            let database = Database::new().await.unwrap();
-            database.migrate().await.ok();
-            let conn = database.conn;
-            let config = Config::get(kwargs!(id == 1), &conn).await.unwrap();
+        let (conn, config, settings, models, prices, conversations, latest) =
+            runtime.block_on(async {
+                let database = Database::new().await.unwrap();
+                database.migrate().await.ok();
+                let conn = database.conn;
+                models::upgrade(&conn).await;
+                let config = Config::get(kwargs!(id == 1), &conn).await.unwrap();
 
-            if config.is_none() {
-                Config::default().save(&conn).await.unwrap();
-            }
+                if config.is_none() {
+                    Config::default().save(&conn).await.unwrap();
+                }
+
+                let settings = Setting::all(&conn).await.unwrap_or_default();
+                let models = ProviderModel::all(&conn).await.unwrap_or_default();
+                let prices = ModelPrice::all(&conn).await.unwrap_or_default();
+                let conversations = Conversation::recent(&conn).await.unwrap_or_default();
+                let latest = match conversations.first() {
+                    Some(conversation) => ChatMessage::of_conversation(conversation.id, &conn)
+                        .await
+                        .ok()
+                        .map(|messages| (conversation.id, messages)),
+                    None => None,
+                };
 
-            (conn, config)
-        });
+                (
+                    conn,
+                    config,
+                    settings,
+                    models,
+                    prices,
+                    conversations,
+                    latest,
+                )
+            });
 
-        let forms = config
+        let mut forms = config
             .as_ref()
             .map(|cfg| {
                 FormState::from([
@@ -55,35 +136,249 @@ impl Default for State {
                 ])
             })
             .unwrap_or_default();
+        forms.extend(
+            settings
+                .into_iter()
+                .map(|setting| (setting.name, setting.value)),
+        );
 
         let ai_choice = config
             .as_ref()
-            .and_then(|cfg| {
-                cfg.ai_choice.as_ref().map(|choice| match choice.as_str() {
-                    "mistral" => AIChoice::Mistral,
-                    "gemini-1.5-flash" => AIChoice::Gemini(Version::V1_5, Gam::Flash),
-                    "gemini-1.5-pro" => AIChoice::Gemini(Version::V1_5, Gam::Pro),
-                    "gemini-2.0-flash" => AIChoice::Gemini(Version::V2_0, Gam::Flash),
-                    "gemini-2.0-pro" => AIChoice::Gemini(Version::V2_0, Gam::Pro),
-                    "gemini-2.5-flash" => AIChoice::Gemini(Version::V2_5, Gam::Flash),
-                    "gemini-2.5-pro" => AIChoice::Gemini(Version::V2_5, Gam::Pro),
-                    _ => panic!("ai choice should 'gemini' or 'mistral'"),
-                })
-            })
+            .and_then(|cfg| cfg.ai_choice.as_deref().and_then(AIChoice::parse))
             .unwrap_or_default();
 
-        Self {
+        let mut state = Self {
             messages: Vec::new(),
             ai_choice: Some(ai_choice),
-            gemini_history: Vec::new(),
-            mistral_history: Vec::new(),
+            models: models
+                .into_iter()
+                .fold(HashMap::new(), |mut models, model| {
+                    models
+                        .entry(model.provider)
+                        .or_insert_with(Vec::new)
+                        .push(ModelInfo {
+                            name: model.name,
+                            context_limit: model.context_limit.map(|limit| limit as u32),
+                        });
+                    models
+                }),
+            pending: None,
+            pending_parsed: None,
+            generation: None,
+            transcript: Vec::new(),
+            excluded: Vec::new(),
+            summarized: false,
+            summary: None,
+            conversation: None,
+            next_conversation: conversations
+                .iter()
+                .map(|conversation| conversation.id)
+                .max()
+                .unwrap_or_default()
+                + 1,
+            conversations,
+            sidebar_open: true,
+            renaming: None,
+            confirm_delete: None,
+            price_inputs: price_inputs_of(&prices),
+            prices,
+            system_prompt: String::new(),
+            params: GenerationParams::default(),
+            param_inputs: params_panel::inputs_of(&GenerationParams::default()),
+            params_open: false,
+            conversation_edits: HashMap::new(),
             content: text_editor::Content::new(),
+            attachments: Vec::new(),
+            attach_error: None,
             screen: Screen::ChatScreen,
             conn,
+            client: fetch::client(),
             forms,
             timer_enabled: false,
             tick: Duration::default(),
             message: String::new(),
+        };
+        if let Some((id, messages)) = latest {
+            state.open_conversation(id, messages);
+        }
+        state
+    }
+}
+
+impl State {
+    /// Shows a saved conversation and rebuilds the context sent with requests.
+    pub fn open_conversation(&mut self, id: Integer, messages: Vec<ChatMessage>) {
+        self.conversation = Some(id);
+        self.messages.clear();
+        self.transcript.clear();
+        self.summary = None;
+        self.excluded.clear();
+        let conversation = self
+            .conversations
+            .iter()
+            .find(|conversation| conversation.id == id);
+        self.system_prompt = conversation
+            .and_then(|conversation| conversation.system_prompt.clone())
+            .unwrap_or_default();
+        let params = conversation
+            .and_then(|conversation| conversation.params.as_deref())
+            .and_then(|params| serde_json::from_str(params).ok())
+            .unwrap_or_default();
+        self.set_params(params);
+
+        for message in messages {
+            if message.role == "user" {
+                // A prompt without reply failed, it left the transcript at the time.
+                self.pop_unanswered_prompt();
+                let attachments: Vec<Attachment> = message
+                    .attachments
+                    .as_deref()
+                    .and_then(|attachments| serde_json::from_str(attachments).ok())
+                    .unwrap_or_default();
+                self.push_message(
+                    MessageType::Sent(attachments.clone()),
+                    message.content.clone(),
+                );
+                self.transcript.push(Turn {
+                    role: Role::User,
+                    content: message.content,
+                    attachments,
+                });
+                continue;
+            }
+
+            let choice = message
+                .model
+                .as_deref()
+                .and_then(AIChoice::parse)
+                .unwrap_or_default();
+            let usage = Usage {
+                input_tokens: message.input_tokens.map(|tokens| tokens as u32),
+                output_tokens: message.output_tokens.map(|tokens| tokens as u32),
+            };
+            let message_type = match message.finish_reason.as_deref() {
+                Some("stopped") => MessageType::Stopped(choice, usage),
+                reason => MessageType::Received(
+                    choice,
+                    usage,
+                    Finish {
+                        reason: reason.map(FinishReason::parse),
+                        safety_ratings: message
+                            .safety_ratings
+                            .as_deref()
+                            .and_then(|ratings| serde_json::from_str(ratings).ok())
+                            .unwrap_or_default(),
+                    },
+                ),
+            };
+            self.push_message(message_type, message.content.clone());
+
+            if message.content.is_empty() {
+                // Same as a reply stopped before any text, see `commit_reply`.
+                self.pop_unanswered_prompt();
+            } else {
+                self.transcript
+                    .push(Turn::new(Role::Assistant, message.content));
+            }
+        }
+    }
+
+    pub fn set_params(&mut self, params: GenerationParams) {
+        self.param_inputs = params_panel::inputs_of(&params);
+        self.params = params;
+    }
+
+    pub fn credentials(&self, provider: &dyn Provider) -> Credentials {
+        let field = |key: &str| self.forms.get(key).cloned().unwrap_or_default();
+        Credentials {
+            api_key: if provider.capabilities().api_key {
+                field(provider.id())
+            } else {
+                String::new()
+            },
+            base_url: field(&provider::base_url_key(provider)),
+            safety_settings: provider
+                .safety_categories()
+                .iter()
+                .map(|(category, _)| {
+                    (
+                        category.to_string(),
+                        field(&provider::safety_key(provider, category)),
+                    )
+                })
+                .filter(|(_, threshold)| !threshold.is_empty())
+                .collect(),
         }
     }
+
+    /// Tokens the context window of `model` holds, from its listing when known.
+    pub fn context_limit(&self, provider: &dyn Provider, model: &str) -> u32 {
+        self.models
+            .get(provider.id())
+            .and_then(|models| models.iter().find(|listed| listed.name == model))
+            .and_then(|listed| listed.context_limit)
+            .unwrap_or_else(|| provider.context_limit(model))
+    }
+
+    pub fn context_strategy(&self) -> (ContextStrategy, usize) {
+        let field = |key: &str| self.forms.get(key).map(|value| value.trim());
+        (
+            field(CONTEXT_STRATEGY_KEY)
+                .and_then(ContextStrategy::parse)
+                .unwrap_or_default(),
+            field(LAST_TURNS_KEY)
+                .and_then(|turns| turns.parse().ok())
+                .unwrap_or(DEFAULT_LAST_TURNS),
+        )
+    }
+
+    pub fn push_message(&mut self, message_type: MessageType, content: String) {
+        let markdown = Markdown::parse(&content);
+        self.messages.push((message_type, content, markdown));
+    }
+
+    /// Index in `messages` of each turn of `transcript`, which skips the
+    /// errors, the prompts they answered and the empty replies.
+    pub fn transcript_messages(&self) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut messages = self.messages.iter().enumerate();
+        for turn in &self.transcript {
+            let found = messages.by_ref().find(|(_, (message_type, content, _))| {
+                let role = match message_type {
+                    MessageType::Sent(_) => Role::User,
+                    MessageType::Received(..) | MessageType::Stopped(..) => Role::Assistant,
+                    MessageType::Error => return false,
+                };
+                role == turn.role && *content == turn.content
+            });
+            match found {
+                Some((index, _)) => indices.push(index),
+                None => break,
+            }
+        }
+        indices
+    }
+
+    pub fn pop_unanswered_prompt(&mut self) {
+        if self
+            .transcript
+            .last()
+            .is_some_and(|turn| turn.role == Role::User)
+        {
+            self.transcript.pop();
+        }
+    }
+}
+
+pub fn price_inputs_of(prices: &[ModelPrice]) -> Vec<(String, String, String)> {
+    prices
+        .iter()
+        .map(|price| {
+            (
+                price.model.clone(),
+                price.input_price.to_string(),
+                price.output_price.to_string(),
+            )
+        })
+        .collect()
 }
@@ -1,12 +1,21 @@
 use iced::{
     border::Radius,
-    widget::{button, container},
-    Background, Border, Color,
+    widget::{button, container, text_input},
+    Background, Border, Color, Theme,
 };
 
 pub const BLUE_SKY: [f32; 3] = [0.8, 0.9, 1.0];
 pub const GRAY: [f32; 3] = [0.9, 0.9, 0.9];
 pub const AI_LABEL_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+pub const ERROR_RED: [f32; 3] = [1.0, 0.88, 0.88];
+pub const FOOTER_GRAY: [f32; 3] = [0.45, 0.45, 0.45];
+pub const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+pub const CODE_BACKGROUND: [f32; 3] = [0.97, 0.97, 0.97];
+pub const INLINE_CODE_BACKGROUND: [f32; 3] = [0.82, 0.82, 0.86];
+pub const QUOTE_BACKGROUND: [f32; 3] = [0.95, 0.95, 0.92];
+pub const QUOTE_BAR: [f32; 3] = [0.6, 0.6, 0.6];
+pub const TABLE_HEADER: [f32; 3] = [0.88, 0.9, 0.95];
+pub const LINK_BLUE: [f32; 3] = [0.1, 0.35, 0.8];
 
 pub fn card(color: [f32; 3]) -> container::Style {
     container::Style {
@@ -19,6 +28,17 @@ pub fn card(color: [f32; 3]) -> container::Style {
     }
 }
 
+pub fn rounded_input(theme: &Theme, status: text_input::Status) -> text_input::Style {
+    text_input::Style {
+        border: Border {
+            width: 2.0,
+            color: Color::from(BLUE_SKY),
+            radius: Radius::from(8.0),
+        },
+        ..text_input::default(theme, status)
+    }
+}
+
 pub fn primary_button(status: button::Status) -> button::Style {
     let (background, text_color, border_color) = match status {
         button::Status::Hovered => ([0.2, 0.6, 1.0], [1.0, 1.0, 1.0], [0.1, 0.5, 0.9]),
@@ -56,3 +76,22 @@ pub fn danger_button(status: button::Status) -> button::Style {
         ..Default::default()
     }
 }
+
+pub fn secondary_button(status: button::Status) -> button::Style {
+    let (background, text_color, border_color) = match status {
+        button::Status::Hovered => ([0.8, 0.9, 1.0], [0.1, 0.3, 0.6], [0.2, 0.6, 1.0]),
+        button::Status::Pressed => ([0.7, 0.85, 1.0], [0.1, 0.3, 0.6], [0.1, 0.5, 0.9]),
+        _ => ([1.0, 1.0, 1.0], [0.1, 0.3, 0.6], [0.3, 0.7, 1.0]),
+    };
+
+    button::Style {
+        background: Some(Background::Color(background.into())),
+        text_color: Color::from_rgb(text_color[0], text_color[1], text_color[2]),
+        border: Border {
+            color: Color::from_rgb(border_color[0], border_color[1], border_color[2]),
+            width: 2.0,
+            radius: Radius::from(8.0), // Rounded corners
+        },
+        ..Default::default()
+    }
+}
@@ -1,19 +1,71 @@
+use base64::prelude::*;
 use iced::time::Duration;
-use iced::widget::text_editor;
-use serde::Deserialize;
-use std::collections::HashMap;
+use iced::widget::{image, text_editor};
+use rusql_alchemy::prelude::Integer;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use crate::{
+    models::ChatMessage,
+    utils::{
+        context::Context,
+        provider::{Delta, ModelInfo, ProviderError},
+    },
+};
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Submit,
     InputTextArea(text_editor::Action),
-    AIRespond(String),
+    AIDelta(Delta),
+    AIDone,
+    AIFailed(ProviderError),
+    /// The turns left out of `context` were summarized, with the tokens spent.
+    Summarized {
+        context: Context,
+        summary: Result<(String, Usage), ProviderError>,
+    },
+    Stop,
+    Retry,
+    Continue,
+    PickAttachments,
+    AttachmentsPicked(Vec<PathBuf>),
+    FileDropped(PathBuf),
+    Attached(Result<Attachment, String>),
+    RemoveAttachment(usize),
+    /// Puts the text in the clipboard.
+    Copy(Arc<str>),
+    /// Puts the text of a shown message in the clipboard, the pending reply
+    /// coming after the others.
+    #[allow(clippy::enum_variant_names)]
+    CopyMessage(usize),
+    /// Opens the URL in the browser.
+    OpenLink(Arc<str>),
+
+    ToggleSidebar,
+    NewConversation,
+    OpenConversation(Integer),
+    ConversationLoaded(Integer, Vec<ChatMessage>),
+    StartRename(Integer),
+    InputRename(String),
+    SaveRename,
+    DeleteConversation(Integer),
+    ConfirmDelete,
+    CancelDelete,
 
     InputForm {
         key: String,
         value: String,
     },
     SaveSetting,
+    InputPrice {
+        index: usize,
+        /// Whether the output price is edited rather than the input one.
+        output: bool,
+        value: String,
+    },
+    AddPrice,
+    RemovePrice(usize),
 
     #[allow(clippy::enum_variant_names)]
     DisplayMessage {
@@ -23,66 +75,370 @@ pub enum Message {
     Tick,
 
     Selected(AIChoice),
+    ToggleParams,
+    InputSystemPrompt(String),
+    InputParam {
+        key: String,
+        value: String,
+    },
+    ApplyPreset(Preset),
+    /// Saves the settings of a conversation if `edit` is still its last edit.
+    SaveConversation {
+        id: Integer,
+        edit: u64,
+    },
+
+    RefreshModels,
+    ModelsListed {
+        provider: String,
+        models: Result<Vec<ModelInfo>, ProviderError>,
+        /// Whether the user asked for the listing, only then a failure is shown.
+        refresh: bool,
+    },
 
     Route(Screen),
 }
 
-#[derive(Deserialize, Clone)]
-pub struct AIMessage {
-    pub role: String,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+/// One turn of the conversation, each provider translates it to its own format.
+#[derive(Clone, Debug)]
+pub struct Turn {
+    pub role: Role,
     pub content: String,
+    pub attachments: Vec<Attachment>,
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq)]
-pub enum Gam {
-    #[default]
-    Flash,
-    Pro,
+impl Turn {
+    pub fn new(role: Role, content: String) -> Self {
+        Self {
+            role,
+            content,
+            attachments: Vec::new(),
+        }
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachments
+            .iter()
+            .filter(|attachment| attachment.is_image())
+    }
+
+    /// Files other than images, sent as text in `content`.
+    pub fn files(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachments
+            .iter()
+            .filter(|attachment| !attachment.is_image())
+    }
 }
 
-impl std::fmt::Display for Gam {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let g = match self {
-            Gam::Flash => "flash",
-            Gam::Pro => "pro",
+/// A file sent with a prompt, saved with it as JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SavedAttachment")]
+pub struct Attachment {
+    pub name: String,
+    /// E.g. `image/png`, `text/plain` for text files and code.
+    pub mime_type: String,
+    /// Content of images in base64, as the providers take it, and text of
+    /// the other files. Shared by the copies the views make.
+    pub data: Arc<str>,
+    /// Size of the file in bytes.
+    pub size: usize,
+    /// Decoded once for the views of an image, a new handle would be
+    /// uploaded again on every frame.
+    #[serde(skip)]
+    pub thumbnail: Option<image::Handle>,
+}
+
+#[derive(Deserialize)]
+struct SavedAttachment {
+    name: String,
+    mime_type: String,
+    data: Arc<str>,
+    #[serde(default)]
+    size: usize,
+}
+
+impl From<SavedAttachment> for Attachment {
+    fn from(saved: SavedAttachment) -> Self {
+        Attachment::new(saved.name, saved.mime_type, saved.data, saved.size)
+    }
+}
+
+impl Attachment {
+    pub fn new(name: String, mime_type: String, data: Arc<str>, size: usize) -> Self {
+        let thumbnail = mime_type
+            .starts_with("image/")
+            .then(|| BASE64_STANDARD.decode(data.as_bytes()).ok())
+            .flatten()
+            .map(image::Handle::from_bytes);
+        Self {
+            name,
+            mime_type,
+            data,
+            size,
+            thumbnail,
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    pub fn data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// Tokens billed for a reply, as reported by the provider.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub input_tokens: Option<u32>,
+    pub output_tokens: Option<u32>,
+}
+
+impl Usage {
+    /// Keeps the latest counts, providers report them across several events.
+    pub fn merge(self, other: Usage) -> Usage {
+        Usage {
+            input_tokens: other.input_tokens.or(self.input_tokens),
+            output_tokens: other.output_tokens.or(self.output_tokens),
+        }
+    }
+
+    /// Adds the counts of another request to the reported ones, the counts
+    /// not reported stay unset.
+    pub fn add(self, other: Usage) -> Usage {
+        let add = |count: Option<u32>, other: Option<u32>| {
+            count.map(|count| count + other.unwrap_or_default())
         };
-        write!(f, "{}", g)
+        Usage {
+            input_tokens: add(self.input_tokens, other.input_tokens),
+            output_tokens: add(self.output_tokens, other.output_tokens),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.input_tokens.is_none() && self.output_tokens.is_none()
     }
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq)]
-pub enum Version {
-    V1_5,
-    #[default]
-    V2_0,
-    V2_5,
+/// How a reply ended, as reported by the provider.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Finish {
+    pub reason: Option<FinishReason>,
+    /// How likely the reply is to be harmful, per category.
+    pub safety_ratings: Vec<SafetyRating>,
 }
 
-impl std::fmt::Display for Version {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let v = match self {
-            Version::V1_5 => "1.5",
-            Version::V2_0 => "2.0",
-            Version::V2_5 => "2.5",
+impl Finish {
+    /// A finish known only by its reason, as the provider names it.
+    pub fn with_reason(reason: &str) -> Finish {
+        Finish {
+            reason: Some(FinishReason::parse(reason)),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the latest reason and ratings, streamed events can lack them.
+    pub fn merge(self, other: Finish) -> Finish {
+        Finish {
+            reason: other.reason.or(self.reason),
+            safety_ratings: if other.safety_ratings.is_empty() {
+                self.safety_ratings
+            } else {
+                other.safety_ratings
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FinishReason {
+    Stop,
+    /// Cut off by the `max_tokens` limit, it can be continued.
+    MaxTokens,
+    Safety,
+    /// Stopped for reciting copyrighted text.
+    Recitation,
+    Other(String),
+}
+
+impl FinishReason {
+    /// Parses a reason as the providers name it, or as saved by `as_str`.
+    pub fn parse(reason: &str) -> Self {
+        match reason.to_ascii_lowercase().as_str() {
+            // Anthropic ends with `end_turn` or `stop_sequence`.
+            "stop" | "end_turn" | "stop_sequence" => FinishReason::Stop,
+            // The OpenAI schema, Mistral and Ollama name the cut `length`.
+            "max_tokens" | "length" => FinishReason::MaxTokens,
+            "safety" => FinishReason::Safety,
+            "recitation" => FinishReason::Recitation,
+            _ => FinishReason::Other(reason.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::MaxTokens => "max_tokens",
+            FinishReason::Safety => "safety",
+            FinishReason::Recitation => "recitation",
+            FinishReason::Other(reason) => reason,
+        }
+    }
+
+    /// Shown next to the author of a reply, `None` when it ended normally.
+    pub fn label(&self) -> Option<String> {
+        match self {
+            FinishReason::Stop => None,
+            FinishReason::MaxTokens => Some("cut off at max tokens".to_string()),
+            FinishReason::Safety => Some("blocked by the safety filters".to_string()),
+            FinishReason::Recitation => Some("blocked for recitation".to_string()),
+            FinishReason::Other(reason) => Some(reason.to_lowercase()),
+        }
+    }
+}
+
+/// Gemini's rating of a reply for one harm category, saved as JSON with the message.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetyRating {
+    /// E.g. `HARM_CATEGORY_HARASSMENT`.
+    pub category: String,
+    /// `NEGLIGIBLE`, `LOW`, `MEDIUM` or `HIGH`.
+    pub probability: String,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// Sampling settings of a conversation, unset ones keep the provider defaults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub stop: Vec<String>,
+    pub seed: Option<u64>,
+}
+
+impl GenerationParams {
+    /// Stop sequences to send, `None` when there are none.
+    pub fn stop(&self) -> Option<&Vec<String>> {
+        (!self.stop.is_empty()).then_some(&self.stop)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    Precise,
+    Balanced,
+    Creative,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Precise, Preset::Balanced, Preset::Creative];
+
+    /// Changes the sampling of `params`, leaving the length and stop settings.
+    pub fn apply(self, params: &GenerationParams) -> GenerationParams {
+        let (temperature, top_p, top_k) = match self {
+            Preset::Precise => (0.2, 0.8, 20),
+            Preset::Balanced => (0.7, 0.95, 40),
+            Preset::Creative => (1.2, 1.0, 100),
         };
-        write!(f, "{}", v)
+        GenerationParams {
+            temperature: Some(temperature),
+            top_p: Some(top_p),
+            top_k: Some(top_k),
+            ..params.clone()
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::Precise => "precise",
+            Preset::Balanced => "balanced",
+            Preset::Creative => "creative",
+        }
     }
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq)]
-pub enum AIChoice {
-    Gemini(Version, Gam),
+/// How the history is cut when it no longer fits the context window of the model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContextStrategy {
+    /// Leaves out the oldest turns.
     #[default]
-    Mistral,
+    DropOldest,
+    /// Keeps only the last turns, as many as set in the settings.
+    LastTurns,
+    /// Replaces the oldest turns with a summary written by the model.
+    Summarize,
+}
+
+impl ContextStrategy {
+    pub const ALL: [ContextStrategy; 3] = [
+        ContextStrategy::DropOldest,
+        ContextStrategy::LastTurns,
+        ContextStrategy::Summarize,
+    ];
+
+    /// Also the value saved in the settings.
+    pub fn label(self) -> &'static str {
+        match self {
+            ContextStrategy::DropOldest => "drop oldest",
+            ContextStrategy::LastTurns => "last turns",
+            ContextStrategy::Summarize => "summarize",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.label() == value)
+    }
+}
+
+/// A model of a registered provider, e.g. `gemini/gemini-2.0-flash`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AIChoice {
+    pub provider: String,
+    pub model: String,
+}
+
+impl AIChoice {
+    pub fn new(provider: &str, model: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// Parses a saved choice, including the `mistral` and `gemini-<version>-<gam>`
+    /// values written by older versions.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.split_once('/') {
+            Some((provider, model)) => Some(Self::new(provider, model)),
+            None if value == "mistral" => Some(Self::default()),
+            None if value.starts_with("gemini-") => Some(Self::new("gemini", value)),
+            None => None,
+        }
+    }
+}
+
+impl Default for AIChoice {
+    fn default() -> Self {
+        Self::new("mistral", "mistral-large-latest")
+    }
 }
 
 impl std::fmt::Display for AIChoice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let name = match self {
-            AIChoice::Gemini(v, g) => &format!("gemini-{v}-{g}"),
-            AIChoice::Mistral => "mistral",
-        };
-        write!(f, "{}", name)
+        write!(f, "{}/{}", self.provider, self.model)
     }
 }
 
@@ -94,8 +450,29 @@ pub enum Screen {
 
 #[derive(Clone, Debug)]
 pub enum MessageType {
-    Sent,
-    Received(AIChoice),
+    Sent(Vec<Attachment>),
+    Received(AIChoice, Usage, Finish),
+    /// A reply interrupted by the user, holding what arrived before.
+    Stopped(AIChoice, Usage),
+    /// A failed request, never part of the history.
+    Error,
+}
+
+impl MessageType {
+    /// Whether this is a reply the model could not finish within `max_tokens`.
+    pub fn cut_off(&self) -> bool {
+        matches!(
+            self,
+            MessageType::Received(
+                _,
+                _,
+                Finish {
+                    reason: Some(FinishReason::MaxTokens),
+                    ..
+                }
+            )
+        )
+    }
 }
 
 pub type FormState = HashMap<String, String>;
@@ -0,0 +1,385 @@
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::provider::{self, Capabilities, Credentials, Delta, Provider, ProviderError, Request};
+use crate::types::{Finish, GenerationParams, Role, Turn, Usage};
+
+const URL: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+/// Required by the API, used unless the conversation sets its own.
+const MAX_TOKENS: u32 = 4096;
+
+const MODELS: &[&str] = &[
+    "claude-sonnet-4-5",
+    "claude-opus-4-1",
+    "claude-3-5-haiku-latest",
+];
+
+#[derive(Deserialize)]
+struct Block {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct TokenUsage {
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+}
+
+impl From<TokenUsage> for Usage {
+    fn from(usage: TokenUsage) -> Self {
+        Usage {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    content: Vec<Block>,
+    usage: Option<TokenUsage>,
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MessageStart {
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+/// One server-sent event, only text deltas, usage and errors matter.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    /// Holds the input tokens.
+    MessageStart {
+        message: MessageStart,
+    },
+    ContentBlockDelta {
+        delta: TextDelta,
+    },
+    /// Holds the output tokens and why the reply ended.
+    MessageDelta {
+        delta: Option<StopDelta>,
+        usage: Option<TokenUsage>,
+    },
+    Error {
+        error: ErrorDetail,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct StopDelta {
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TextDelta {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+pub struct Anthropic;
+
+impl Anthropic {
+    /// Content blocks of a turn, its images ahead of the text they are asked about.
+    fn blocks(turn: &Turn) -> Vec<Value> {
+        let images = turn.images().map(|image| {
+            json!({
+                "type": "image",
+                "source": {
+                    "type": "base64",
+                    "media_type": image.mime_type,
+                    "data": image.data
+                }
+            })
+        });
+        let text =
+            (!turn.content.is_empty()).then(|| json!({ "type": "text", "text": turn.content }));
+        images.chain(text).collect()
+    }
+
+    fn error(status: Option<StatusCode>, error: ErrorDetail) -> ProviderError {
+        match error.kind.as_str() {
+            "authentication_error" | "permission_error" => ProviderError::Auth(error.message),
+            "rate_limit_error" => ProviderError::Quota(error.message),
+            "invalid_request_error" => ProviderError::BadRequest(error.message),
+            _ => match status {
+                Some(status) => ProviderError::from_status(status, error.message),
+                None => ProviderError::Network(error.message),
+            },
+        }
+    }
+}
+
+impl Provider for Anthropic {
+    fn id(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn name(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            api_key: true,
+            base_url: false,
+            custom_model: false,
+            streaming: true,
+        }
+    }
+
+    fn models(&self) -> &'static [&'static str] {
+        MODELS
+    }
+
+    fn context_limit(&self, _model: &str) -> u32 {
+        200_000
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError> {
+        // Turns have to alternate, consecutive ones of the same role are merged.
+        let mut messages: Vec<(Role, Vec<Value>)> = Vec::new();
+        for turn in transcript {
+            let blocks = Self::blocks(turn);
+            match messages.last_mut() {
+                Some((role, content)) if *role == turn.role => content.extend(blocks),
+                _ => messages.push((turn.role, blocks)),
+            }
+        }
+        let messages = messages
+            .into_iter()
+            .map(|(role, content)| {
+                let role = match role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                json!({
+                    "role": role,
+                    "content": content
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut headers = HeaderMap::new();
+        let api_key = HeaderValue::from_str(&credentials.api_key)
+            .map_err(|_| ProviderError::Auth("the API key is not a valid header value".into()))?;
+        headers.insert("x-api-key", api_key);
+        headers.insert("anthropic-version", HeaderValue::from_static(API_VERSION));
+
+        let mut body = json!({
+            "model": model,
+            "max_tokens": params.max_tokens.unwrap_or(MAX_TOKENS),
+            "messages": messages,
+            "stream": stream,
+        });
+        provider::set(
+            &mut body,
+            "system",
+            (!system.trim().is_empty()).then_some(system),
+        );
+        // The temperature only goes up to 1, and the recent models take
+        // either it or `top_p`, not both.
+        let temperature = params
+            .temperature
+            .map(|temperature| temperature.clamp(0.0, 1.0));
+        provider::set(&mut body, "temperature", temperature);
+        if temperature.is_none() {
+            provider::set(&mut body, "top_p", params.top_p);
+        }
+        provider::set(&mut body, "top_k", params.top_k);
+        provider::set(&mut body, "stop_sequences", params.stop());
+
+        Ok(Request {
+            url: URL.to_string(),
+            headers: Some(headers),
+            body,
+        })
+    }
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError> {
+        let response: Response = serde_json::from_value(response)?;
+        Ok(Delta {
+            text: response
+                .content
+                .into_iter()
+                .filter(|block| block.kind == "text")
+                .map(|block| block.text)
+                .collect(),
+            usage: response.usage.map(Usage::from).unwrap_or_default(),
+            finish: response.stop_reason.as_deref().map(Finish::with_reason),
+        })
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Option<Delta>, ProviderError> {
+        let usage = |usage: Option<TokenUsage>| Delta {
+            text: String::new(),
+            usage: usage.map(Usage::from).unwrap_or_default(),
+            finish: None,
+        };
+        match serde_json::from_str(data)? {
+            Event::MessageStart { message } => Ok(usage(message.usage).non_empty()),
+            Event::ContentBlockDelta { delta } => Ok(delta.text.map(Delta::text)),
+            Event::MessageDelta {
+                delta,
+                usage: tokens,
+            } => Ok(Delta {
+                finish: delta
+                    .and_then(|delta| delta.stop_reason)
+                    .as_deref()
+                    .map(Finish::with_reason),
+                ..usage(tokens)
+            }
+            .non_empty()),
+            // Errors such as an overloaded server can arrive after a 200.
+            Event::Error { error } => Err(Self::error(None, error)),
+            Event::Other => Ok(None),
+        }
+    }
+
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        match serde_json::from_str::<ErrorBody>(body) {
+            Ok(ErrorBody { error }) => Self::error(Some(status), error),
+            Err(_) => ProviderError::from_status(status, body.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FinishReason, Preset};
+
+    fn event(data: &str) -> Option<Delta> {
+        Anthropic.parse_stream_event(data).unwrap()
+    }
+
+    #[test]
+    fn stream_events() {
+        let start = event(
+            r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":25,"output_tokens":1}}}"#,
+        )
+        .unwrap();
+        assert_eq!(start.usage.input_tokens, Some(25));
+
+        assert!(event(
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#
+        )
+        .is_none());
+        let text = event(
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hello"}}"#,
+        )
+        .unwrap();
+        assert_eq!(text.text, "Hello");
+
+        let end = event(
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#,
+        )
+        .unwrap();
+        assert_eq!(end.usage.output_tokens, Some(15));
+        assert_eq!(end.finish.unwrap().reason, Some(FinishReason::Stop));
+        assert!(event(r#"{"type":"ping"}"#).is_none());
+        assert!(event(r#"{"type":"message_stop"}"#).is_none());
+    }
+
+    #[test]
+    fn stream_cut_by_max_tokens() {
+        let end = event(
+            r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens","stop_sequence":null},"usage":{"output_tokens":1024}}"#,
+        )
+        .unwrap();
+        assert_eq!(end.finish.unwrap().reason, Some(FinishReason::MaxTokens));
+    }
+
+    #[test]
+    fn request_fits_the_sampling_limits() {
+        let params = Preset::Creative.apply(&GenerationParams::default());
+        let request = Anthropic
+            .build_request(
+                "claude-sonnet-4-5",
+                "",
+                &[Turn::new(Role::User, "Hi".to_string())],
+                &Credentials::default(),
+                &params,
+                true,
+            )
+            .unwrap();
+        assert_eq!(request.body["temperature"], json!(1.0));
+        assert!(request.body.get("top_p").is_none());
+        assert_eq!(request.body["top_k"], json!(100));
+
+        let params = GenerationParams {
+            top_p: Some(0.9),
+            ..Default::default()
+        };
+        let request = Anthropic
+            .build_request(
+                "claude-sonnet-4-5",
+                "",
+                &[],
+                &Credentials::default(),
+                &params,
+                true,
+            )
+            .unwrap();
+        assert_eq!(request.body["top_p"], json!(0.9));
+    }
+
+    #[test]
+    fn stream_error_after_success() {
+        let error = Anthropic
+            .parse_stream_event(
+                r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            )
+            .unwrap_err();
+        assert!(matches!(error, ProviderError::Network(message) if message == "Overloaded"));
+    }
+
+    #[test]
+    fn whole_response_keeps_text_blocks() {
+        let delta = Anthropic
+            .parse_response(json!({
+                "content": [
+                    {"type": "text", "text": "Hello"},
+                    {"type": "tool_use", "id": "1"},
+                    {"type": "text", "text": " world"},
+                ],
+                "usage": {"input_tokens": 3, "output_tokens": 2},
+                "stop_reason": "max_tokens",
+            }))
+            .unwrap();
+        assert_eq!(delta.text, "Hello world");
+        assert_eq!(delta.usage.input_tokens, Some(3));
+        assert_eq!(delta.finish.unwrap().reason, Some(FinishReason::MaxTokens));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::types::{Attachment, ContextStrategy, Role, Turn};
+
+/// Tokens kept for the reply when the conversation does not set `max_tokens`.
+const REPLY_TOKENS: u32 = 1024;
+/// Tokens each message costs besides its text, for the role and delimiters.
+const TURN_OVERHEAD: u32 = 4;
+/// Rough cost of an image, providers count between a few hundred and 1600 tokens.
+const IMAGE_TOKENS: u32 = 1000;
+
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the following conversation in a few \
+    paragraphs. Keep the facts, decisions and open questions needed to continue it.";
+/// Longest summary asked for, it is sent again each time it is extended.
+pub const SUMMARY_TOKENS: u32 = 1024;
+
+/// The turns of a request once fitted in the context window.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    /// Sent as is, the prompt last.
+    pub transcript: Vec<Turn>,
+    /// Summarized before the request, empty unless the strategy summarizes.
+    pub earlier: Vec<Turn>,
+    /// Leading turns of the history left out of `transcript`.
+    pub excluded: usize,
+}
+
+/// Rough token count, tokenizers average about four characters per token.
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/// Rough tokens an attached file adds to the prompt.
+pub fn attachment_tokens(attachment: &Attachment) -> u32 {
+    if attachment.is_image() {
+        IMAGE_TOKENS
+    } else {
+        estimate_tokens(&attachment.data)
+    }
+}
+
+fn turn_tokens(turn: &Turn) -> u32 {
+    estimate_tokens(&turn.content)
+        + turn.attachments.iter().map(attachment_tokens).sum::<u32>()
+        + TURN_OVERHEAD
+}
+
+/// Moves the attached files other than images into the text of `turn`,
+/// each in a block naming it, ahead of the prompt.
+pub fn inline_files(mut turn: Turn) -> Turn {
+    if turn.files().next().is_none() {
+        return turn;
+    }
+    let mut content = String::new();
+    for file in turn.files() {
+        content.push_str(&format!(
+            "<file name=\"{}\">\n{}\n</file>\n\n",
+            file.name, file.data
+        ));
+    }
+    content.push_str(&turn.content);
+    turn.content = content;
+    turn.attachments.retain(Attachment::is_image);
+    turn
+}
+
+/// Cuts `transcript` following `strategy` when it does not fit in `limit` tokens
+/// with the `system` instructions and room for the reply.
+///
+/// The prompt is always sent, even alone over the limit, and the kept turns
+/// start with a user one as some providers require.
+pub fn fit(
+    system: &str,
+    transcript: Vec<Turn>,
+    limit: u32,
+    max_tokens: Option<u32>,
+    strategy: ContextStrategy,
+    last_turns: usize,
+) -> Context {
+    let reply = max_tokens.unwrap_or(REPLY_TOKENS).min(limit / 2);
+    let budget = limit.saturating_sub(reply + estimate_tokens(system));
+    let total: u32 = transcript.iter().map(turn_tokens).sum();
+    if total <= budget {
+        return Context {
+            transcript,
+            ..Default::default()
+        };
+    }
+
+    let (first, budget) = match strategy {
+        ContextStrategy::DropOldest => (0, budget),
+        // The prompt is kept even when no turn is set to be.
+        ContextStrategy::LastTurns => (transcript.len().saturating_sub(last_turns.max(1)), budget),
+        // Leaves room for the summary in the instructions.
+        ContextStrategy::Summarize => (0, budget / 4 * 3),
+    };
+    let mut start = first;
+    let mut tokens: u32 = transcript[start..].iter().map(turn_tokens).sum();
+    while tokens > budget && start + 1 < transcript.len() {
+        tokens -= turn_tokens(&transcript[start]);
+        start += 1;
+    }
+    while start + 1 < transcript.len() && transcript[start].role != Role::User {
+        start += 1;
+    }
+
+    let mut transcript = transcript;
+    let kept = transcript.split_off(start);
+    Context {
+        transcript: kept,
+        earlier: match strategy {
+            ContextStrategy::Summarize => transcript,
+            _ => Vec::new(),
+        },
+        excluded: start,
+    }
+}
+
+/// Splits the `earlier` turns into runs each summarized by one request within
+/// `limit` tokens, with room for the summary it extends and the new one.
+/// A turn too long alone is cut.
+pub fn summary_chunks(earlier: &[Turn], limit: u32) -> Vec<Vec<Turn>> {
+    let budget = limit
+        .saturating_sub(2 * SUMMARY_TOKENS + estimate_tokens(SUMMARY_INSTRUCTIONS))
+        .max(limit / 4);
+    let mut chunks: Vec<Vec<Turn>> = Vec::new();
+    let mut tokens = 0;
+    for turn in earlier {
+        // Only the text is summarized.
+        let mut turn = Turn::new(turn.role, turn.content.clone());
+        if estimate_tokens(&turn.content) + TURN_OVERHEAD > budget {
+            let chars = budget.saturating_sub(TURN_OVERHEAD) as usize * 4;
+            turn.content = turn.content.chars().take(chars).collect();
+        }
+        let cost = turn_tokens(&turn);
+        match chunks.last_mut() {
+            Some(chunk) if tokens + cost <= budget => chunk.push(turn),
+            _ => {
+                chunks.push(vec![turn]);
+                tokens = 0;
+            }
+        }
+        tokens += cost;
+    }
+    chunks
+}
+
+/// The request asking the model to summarize the `earlier` turns, extending
+/// the `previous` summary of the turns before them.
+pub fn summary_request(previous: Option<&str>, earlier: &[Turn]) -> Vec<Turn> {
+    let conversation = previous
+        .map(|summary| format!("Summary of the conversation so far:\n{summary}"))
+        .into_iter()
+        .chain(earlier.iter().map(|turn| {
+            let author = match turn.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+            };
+            format!("{author}: {}", turn.content)
+        }))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    vec![Turn::new(
+        Role::User,
+        format!("{SUMMARY_INSTRUCTIONS}\n\n{conversation}"),
+    )]
+}
+
+/// The `system` instructions followed by the summary of the turns left out.
+pub fn with_summary(system: &str, summary: &str) -> String {
+    let summary = format!("Summary of the earlier conversation:\n{summary}");
+    match system.trim() {
+        "" => summary,
+        system => format!("{system}\n\n{summary}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Alternating user and assistant turns of 100 tokens, 104 with the overhead.
+    fn transcript(turns: usize) -> Vec<Turn> {
+        (0..turns)
+            .map(|i| {
+                let role = if i % 2 == 0 {
+                    Role::User
+                } else {
+                    Role::Assistant
+                };
+                Turn::new(role, format!("{i:<400}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_a_transcript_that_fits() {
+        let context = fit(
+            "",
+            transcript(5),
+            4096,
+            None,
+            ContextStrategy::DropOldest,
+            0,
+        );
+        assert_eq!(context.transcript.len(), 5);
+        assert!(context.earlier.is_empty());
+        assert_eq!(context.excluded, 0);
+    }
+
+    #[test]
+    fn drops_the_oldest_turns() {
+        // 520 tokens left once the reply has its 500, five turns.
+        let context = fit(
+            "",
+            transcript(9),
+            1020,
+            Some(500),
+            ContextStrategy::DropOldest,
+            0,
+        );
+        assert_eq!(context.excluded, 4);
+        assert_eq!(context.transcript.len(), 5);
+        assert!(context.transcript[0].content.starts_with('4'));
+        assert!(context.earlier.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_last_turns() {
+        let context = fit(
+            "",
+            transcript(9),
+            1020,
+            Some(500),
+            ContextStrategy::LastTurns,
+            3,
+        );
+        assert_eq!(context.excluded, 6);
+        assert_eq!(context.transcript.len(), 3);
+    }
+
+    #[test]
+    fn keeps_the_prompt_when_no_turn_is_kept() {
+        let context = fit(
+            "",
+            transcript(9),
+            1020,
+            Some(500),
+            ContextStrategy::LastTurns,
+            0,
+        );
+        assert_eq!(context.excluded, 8);
+        assert_eq!(context.transcript.len(), 1);
+        assert!(context.transcript[0].content.starts_with('8'));
+    }
+
+    #[test]
+    fn summarizes_with_room_for_the_summary() {
+        let context = fit(
+            "",
+            transcript(9),
+            1020,
+            Some(500),
+            ContextStrategy::Summarize,
+            0,
+        );
+        // 3/4 of the 520 tokens hold three turns.
+        assert_eq!(context.excluded, 6);
+        assert_eq!(context.transcript.len(), 3);
+        assert_eq!(context.earlier.len(), 6);
+    }
+
+    #[test]
+    fn sends_the_prompt_alone_over_the_limit() {
+        let context = fit("", transcript(3), 100, None, ContextStrategy::DropOldest, 0);
+        assert_eq!(context.excluded, 2);
+        assert_eq!(context.transcript.len(), 1);
+    }
+
+    #[test]
+    fn counts_the_system_instructions_and_starts_with_a_user_turn() {
+        let system = "x".repeat(2000);
+        let context = fit(
+            &system,
+            transcript(5),
+            1500,
+            Some(500),
+            ContextStrategy::DropOldest,
+            0,
+        );
+        // One turn over, the kept ones start with a user turn.
+        assert_eq!(context.excluded, 2);
+        assert_eq!(context.transcript[0].role, Role::User);
+    }
+
+    #[test]
+    fn summary_chunks_fit_the_limit() {
+        // 4096 - 2048 - 26 tokens for each summary request.
+        let chunks = summary_chunks(&transcript(40), 4096);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.iter().map(Vec::len).sum::<usize>(), 40);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 19));
+    }
+
+    #[test]
+    fn summary_chunks_cut_a_long_turn() {
+        let long = Turn::new(Role::User, "x".repeat(40_000));
+        let chunks = summary_chunks(&[long], 4096);
+        assert_eq!(chunks.len(), 1);
+        assert!(turn_tokens(&chunks[0][0]) <= 2022);
+    }
+
+    #[test]
+    fn summary_request_extends_the_previous_summary() {
+        let request = summary_request(Some("They met."), &transcript(1));
+        assert_eq!(request.len(), 1);
+        let content = &request[0].content;
+        assert!(content.contains("Summary of the conversation so far:\nThey met."));
+        assert!(content.ends_with(&format!("User: {:<400}", 0)));
+    }
+}
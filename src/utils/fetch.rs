@@ -1,18 +1,165 @@
-use anyhow::Result;
-use reqwest::header::HeaderMap;
+use iced::futures::{SinkExt, Stream, StreamExt};
+use iced::time::Duration;
+use reqwest::{header::HeaderMap, Client, StatusCode};
 use serde::Deserialize;
 use serde_json::Value;
 
+use super::{ndjson::NdjsonDecoder, sse::SseDecoder};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum silence between two reads, thinking models can be slow to start.
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug)]
+pub enum FetchError {
+    Timeout,
+    Network(reqwest::Error),
+    Status { status: StatusCode, body: String },
+    Decode(serde_json::Error),
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Timeout => write!(f, "the request timed out"),
+            FetchError::Network(err) => write!(f, "network error: {err}"),
+            FetchError::Status { status, body } => write!(f, "{status}: {body}"),
+            FetchError::Decode(err) => write!(f, "invalid response: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            FetchError::Timeout
+        } else {
+            FetchError::Network(err)
+        }
+    }
+}
+
+impl From<serde_json::Error> for FetchError {
+    fn from(err: serde_json::Error) -> Self {
+        FetchError::Decode(err)
+    }
+}
+
+/// How a streamed body is split into events.
+#[derive(Clone, Copy, Debug)]
+pub enum StreamFormat {
+    /// Server-sent events, each yielding its `data` payload.
+    Sse,
+    /// Newline-delimited JSON, each yielding one line.
+    Ndjson,
+}
+
+enum Decoder {
+    Sse(SseDecoder),
+    Ndjson(NdjsonDecoder),
+}
+
+impl Decoder {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        match self {
+            Decoder::Sse(decoder) => decoder.push(chunk),
+            Decoder::Ndjson(decoder) => decoder.push(chunk),
+        }
+    }
+
+    fn finish(self) -> Option<String> {
+        match self {
+            Decoder::Sse(decoder) => decoder.finish(),
+            Decoder::Ndjson(decoder) => decoder.finish(),
+        }
+    }
+}
+
+/// Builds the HTTP client shared by every provider, its connections are pooled.
+pub fn client() -> Client {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .build()
+        .expect("the http client should build with static settings")
+}
+
+async fn send(
+    request: reqwest::RequestBuilder,
+    headers: Option<HeaderMap>,
+) -> Result<reqwest::Response, FetchError> {
+    let request = match headers {
+        Some(headers) => request.headers(headers),
+        None => request,
+    };
+    let response = request.send().await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(FetchError::Status { status, body });
+    }
+    Ok(response)
+}
+
+/// Posts `body` as JSON and decodes the response.
+///
+/// Dropping the returned future cancels the request.
 pub async fn fetch<T: for<'de> Deserialize<'de>>(
+    client: &Client,
     url: &str,
     body: Value,
     headers: Option<HeaderMap>,
-) -> Result<T> {
-    let client = reqwest::blocking::Client::new();
-    let mut request = client.post(url).json(&body);
-    if let Some(headers) = headers {
-        request = request.headers(headers);
-    }
-    let response = request.send()?;
-    Ok(response.json()?)
+) -> Result<T, FetchError> {
+    let response = send(client.post(url).json(&body), headers).await?;
+    let bytes = response.bytes().await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Gets `url` and decodes the response.
+pub async fn fetch_get<T: for<'de> Deserialize<'de>>(
+    client: &Client,
+    url: &str,
+    headers: Option<HeaderMap>,
+) -> Result<T, FetchError> {
+    let response = send(client.get(url), headers).await?;
+    let bytes = response.bytes().await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Sends the request and yields each event of the streamed body.
+///
+/// The body is read while the stream is polled, dropping it closes the connection.
+pub async fn fetch_stream(
+    client: &Client,
+    url: &str,
+    body: Value,
+    headers: Option<HeaderMap>,
+    format: StreamFormat,
+) -> Result<impl Stream<Item = Result<String, FetchError>>, FetchError> {
+    let response = send(client.post(url).json(&body), headers).await?;
+
+    let mut bytes = response.bytes_stream();
+    let mut decoder = match format {
+        StreamFormat::Sse => Decoder::Sse(SseDecoder::default()),
+        StreamFormat::Ndjson => Decoder::Ndjson(NdjsonDecoder::default()),
+    };
+    let events = iced::stream::try_channel(16, move |mut output| async move {
+        while let Some(chunk) = bytes.next().await {
+            for event in decoder.push(&chunk?) {
+                if output.send(event).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(event) = decoder.finish() {
+            output.send(event).await.ok();
+        }
+        Ok(())
+    });
+    Ok(events)
 }
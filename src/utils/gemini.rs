@@ -1,64 +1,383 @@
-use super::fetch::fetch;
-use anyhow::Result;
+use reqwest::StatusCode;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 
-use crate::types::{AIMessage, Gam, Version};
+use super::provider::{
+    self, Capabilities, Credentials, Delta, ModelInfo, Provider, ProviderError, Request,
+};
+use crate::types::{Finish, FinishReason, GenerationParams, Role, SafetyRating, Turn, Usage};
+
+const BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Shown until the models are listed with the API key.
+const MODELS: &[&str] = &[
+    "gemini-1.5-flash",
+    "gemini-1.5-pro",
+    "gemini-2.0-flash",
+    "gemini-2.0-pro",
+    "gemini-2.5-flash",
+    "gemini-2.5-pro",
+];
+
+const SAFETY_CATEGORIES: &[(&str, &str)] = &[
+    ("HARM_CATEGORY_HARASSMENT", "Harassment"),
+    ("HARM_CATEGORY_HATE_SPEECH", "Hate speech"),
+    ("HARM_CATEGORY_SEXUALLY_EXPLICIT", "Sexually explicit"),
+    ("HARM_CATEGORY_DANGEROUS_CONTENT", "Dangerous content"),
+    ("HARM_CATEGORY_CIVIC_INTEGRITY", "Civic integrity"),
+];
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Part {
+    #[serde(default)]
     pub text: String,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Content {
+    #[serde(default)]
     pub parts: Vec<Part>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Candidate {
-    pub content: Content,
+    #[serde(default)]
+    pub content: Option<Content>,
+    /// Only on the last event of a streamed reply.
+    pub finish_reason: Option<String>,
+    #[serde(default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+impl Candidate {
+    /// Every text part, replies with code or citations come in several.
+    fn text(&self) -> String {
+        self.content
+            .as_ref()
+            .map(|content| {
+                content
+                    .parts
+                    .iter()
+                    .map(|part| part.text.as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn finish(&self) -> Option<Finish> {
+        if self.finish_reason.is_none() && self.safety_ratings.is_empty() {
+            return None;
+        }
+        Some(Finish {
+            reason: self.finish_reason.as_deref().map(FinishReason::parse),
+            safety_ratings: self.safety_ratings.clone(),
+        })
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+    pub block_reason: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageMetadata {
+    pub prompt_token_count: Option<u32>,
+    pub candidates_token_count: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct Response {
+    #[serde(default)]
     pub candidates: Vec<Candidate>,
+    pub prompt_feedback: Option<PromptFeedback>,
+    /// Counts so far, every streamed event repeats them.
+    pub usage_metadata: Option<UsageMetadata>,
 }
 
-pub async fn ask_gemini(
-    v: Version,
-    g: Gam,
-    text: String,
-    history: Vec<AIMessage>,
-    api_key: String,
-) -> Result<String> {
-    let mut contents = history
-        .iter()
-        .map(|msg| {
-            json!({
-                "role": msg.role,
-                "parts": [json!({"text": msg.content})]
+impl Response {
+    fn usage(&self) -> Usage {
+        self.usage_metadata
+            .as_ref()
+            .map(|usage| Usage {
+                input_tokens: usage.prompt_token_count,
+                output_tokens: usage.candidates_token_count,
             })
+            .unwrap_or_default()
+    }
+
+    fn delta(&self) -> Delta {
+        let candidate = self.candidates.first();
+        Delta {
+            text: candidate.map(Candidate::text).unwrap_or_default(),
+            usage: self.usage(),
+            finish: candidate.and_then(Candidate::finish),
+        }
+    }
+
+    /// A prompt refused by the safety filters comes back without any candidate.
+    fn blocked(&self) -> Option<ProviderError> {
+        let reason = self.prompt_feedback.as_ref()?.block_reason.as_ref()?;
+        self.candidates
+            .is_empty()
+            .then(|| ProviderError::Safety(format!("the prompt was blocked ({reason})")))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Model {
+    /// `models/<id>`.
+    name: String,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+    input_token_limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct Models {
+    #[serde(default)]
+    models: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+    #[serde(default)]
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+pub struct Gemini;
+
+impl Provider for Gemini {
+    fn id(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            api_key: true,
+            base_url: false,
+            custom_model: false,
+            streaming: true,
+        }
+    }
+
+    fn models(&self) -> &'static [&'static str] {
+        MODELS
+    }
+
+    fn models_request(&self, credentials: &Credentials) -> Option<Request> {
+        (!credentials.api_key.is_empty()).then(|| Request {
+            url: format!(
+                "{BASE_URL}/models?pageSize=1000&key={}",
+                credentials.api_key
+            ),
+            headers: None,
+            body: Value::Null,
         })
-        .collect::<Vec<_>>();
+    }
+
+    fn parse_models(&self, response: Value) -> Result<Vec<ModelInfo>, ProviderError> {
+        let models: Models = serde_json::from_value(response)?;
+        Ok(models
+            .models
+            .into_iter()
+            .filter(|model| {
+                model
+                    .supported_generation_methods
+                    .iter()
+                    .any(|method| method == "generateContent")
+            })
+            .map(|model| ModelInfo {
+                name: model
+                    .name
+                    .strip_prefix("models/")
+                    .map(str::to_string)
+                    .unwrap_or(model.name),
+                context_limit: model.input_token_limit,
+            })
+            .collect())
+    }
+
+    fn safety_categories(&self) -> &'static [(&'static str, &'static str)] {
+        SAFETY_CATEGORIES
+    }
+
+    fn safety_thresholds(&self) -> &'static [&'static str] {
+        &[
+            "BLOCK_NONE",
+            "BLOCK_ONLY_HIGH",
+            "BLOCK_MEDIUM_AND_ABOVE",
+            "BLOCK_LOW_AND_ABOVE",
+            "OFF",
+        ]
+    }
+
+    fn context_limit(&self, _model: &str) -> u32 {
+        1_048_576
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError> {
+        let contents = transcript
+            .iter()
+            .map(|turn| {
+                let role = match turn.role {
+                    Role::User => "user",
+                    Role::Assistant => "model",
+                };
+                let text = (!turn.content.is_empty() || turn.attachments.is_empty())
+                    .then(|| json!({ "text": turn.content }));
+                let images = turn.images().map(|image| {
+                    json!({
+                        "inline_data": {
+                            "mime_type": image.mime_type,
+                            "data": image.data
+                        }
+                    })
+                });
+                json!({
+                    "role": role,
+                    "parts": text.into_iter().chain(images).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
 
-    contents.push(json!({
-        "role": "user",
-        "parts": [json!({"text": text})]
-    }));
+        let method = if stream {
+            "streamGenerateContent?alt=sse&"
+        } else {
+            "generateContent?"
+        };
 
-    let body = json!({ "contents": contents });
+        let mut generation_config = json!({});
+        provider::set(&mut generation_config, "temperature", params.temperature);
+        provider::set(&mut generation_config, "topP", params.top_p);
+        provider::set(&mut generation_config, "topK", params.top_k);
+        provider::set(&mut generation_config, "maxOutputTokens", params.max_tokens);
+        provider::set(&mut generation_config, "stopSequences", params.stop());
+        provider::set(&mut generation_config, "seed", params.seed);
+        let mut body = json!({ "contents": contents });
+        if !system.trim().is_empty() {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if generation_config != json!({}) {
+            body["generationConfig"] = generation_config;
+        }
+        if !credentials.safety_settings.is_empty() {
+            body["safetySettings"] = credentials
+                .safety_settings
+                .iter()
+                .map(|(category, threshold)| {
+                    json!({
+                        "category": category,
+                        "threshold": threshold
+                    })
+                })
+                .collect();
+        }
 
-    let url = format!("https://generativelanguage.googleapis.com/v1beta/models/gemini-{v}-{g}:generateContent?key={api_key}");
+        Ok(Request {
+            url: format!(
+                "{BASE_URL}/models/{model}:{method}key={}",
+                credentials.api_key
+            ),
+            headers: None,
+            body,
+        })
+    }
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError> {
+        let response: Response = serde_json::from_value(response)?;
+        if let Some(err) = response.blocked() {
+            return Err(err);
+        }
+        Ok(response.delta())
+    }
 
-    let response: Response = fetch(&url, body, None).await?;
+    fn parse_stream_event(&self, data: &str) -> Result<Option<Delta>, ProviderError> {
+        // Each event is a whole `GenerateContentResponse` holding only the new parts.
+        let response: Response = serde_json::from_str(data)?;
+        if let Some(err) = response.blocked() {
+            return Err(err);
+        }
+        Ok(response.delta().non_empty())
+    }
 
-    let mut output = String::new();
-    if let Some(candidate) = response.candidates.first() {
-        if let Some(part) = candidate.content.parts.first() {
-            output = part.text.clone();
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        let Ok(ErrorBody { error }) = serde_json::from_str(body) else {
+            return ProviderError::from_status(status, body.to_string());
+        };
+        // An invalid key is reported as a bad request rather than a 401.
+        if error.status == "INVALID_ARGUMENT" && error.message.contains("API key") {
+            return ProviderError::Auth(error.message);
         }
+        ProviderError::from_status(status, error.message)
     }
+}
 
-    Ok(output)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_models_that_generate() {
+        let models = Gemini
+            .parse_models(json!({"models": [
+                {
+                    "name": "models/gemini-2.5-flash",
+                    "supportedGenerationMethods": ["generateContent", "countTokens"],
+                    "inputTokenLimit": 1048576,
+                },
+                {
+                    "name": "models/text-embedding-004",
+                    "supportedGenerationMethods": ["embedContent"],
+                },
+            ]}))
+            .unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].name, "gemini-2.5-flash");
+        assert_eq!(models[0].context_limit, Some(1_048_576));
+    }
+
+    #[test]
+    fn stream_event_text_and_finish() {
+        let delta = Gemini
+            .parse_stream_event(
+                r#"{"candidates":[{"content":{"parts":[{"text":"Hel"},{"text":"lo"}],"role":"model"},"finishReason":"MAX_TOKENS"}],"usageMetadata":{"promptTokenCount":4,"candidatesTokenCount":2}}"#,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.text, "Hello");
+        assert_eq!(delta.usage.output_tokens, Some(2));
+        assert_eq!(delta.finish.unwrap().reason, Some(FinishReason::MaxTokens));
+    }
+
+    #[test]
+    fn blocked_prompt() {
+        let error = Gemini
+            .parse_stream_event(r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#)
+            .unwrap_err();
+        assert!(matches!(error, ProviderError::Safety(_)));
+    }
 }
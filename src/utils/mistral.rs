@@ -1,53 +1,171 @@
-use super::fetch::fetch;
-use anyhow::Result;
-use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::StatusCode;
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 
-use crate::types::AIMessage;
+use super::{
+    openai::{self, ImageFormat},
+    provider::{Capabilities, Credentials, Delta, ModelInfo, Provider, ProviderError, Request},
+};
+use crate::types::{GenerationParams, Turn};
 
 const URL: &str = "https://api.mistral.ai/v1/chat/completions";
+const MODELS_URL: &str = "https://api.mistral.ai/v1/models";
+
+/// Shown until the models are listed with the API key.
+const MODELS: &[&str] = &["mistral-large-latest"];
 
 #[derive(Deserialize)]
-struct Choice {
-    message: AIMessage,
+struct ModelCapabilities {
+    #[serde(default)]
+    completion_chat: bool,
 }
 
 #[derive(Deserialize)]
-struct Response {
-    choices: Vec<Choice>,
+struct Model {
+    id: String,
+    #[serde(default)]
+    capabilities: Option<ModelCapabilities>,
+    max_context_length: Option<u32>,
 }
 
-pub async fn ask_mistral(text: String, history: Vec<AIMessage>, api_key: String) -> Result<String> {
-    let mut messages = history
-        .iter()
-        .map(|msg| {
-            json!({
-                "role": msg.role.clone(),
-                "content": msg.content.clone()
-            })
+#[derive(Deserialize)]
+struct Models {
+    data: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    /// A string, or an object describing the invalid fields.
+    message: Value,
+}
+
+pub struct Mistral;
+
+impl Provider for Mistral {
+    fn id(&self) -> &'static str {
+        "mistral"
+    }
+
+    fn name(&self) -> &'static str {
+        "Mistral"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            api_key: true,
+            base_url: false,
+            custom_model: false,
+            streaming: true,
+        }
+    }
+
+    fn models(&self) -> &'static [&'static str] {
+        MODELS
+    }
+
+    fn models_request(&self, credentials: &Credentials) -> Option<Request> {
+        if credentials.api_key.is_empty() {
+            return None;
+        }
+        Some(Request {
+            url: MODELS_URL.to_string(),
+            headers: openai::bearer(&credentials.api_key).ok(),
+            body: Value::Null,
         })
-        .collect::<Vec<_>>();
+    }
+
+    fn parse_models(&self, response: Value) -> Result<Vec<ModelInfo>, ProviderError> {
+        let models: Models = serde_json::from_value(response)?;
+        // Embedding and moderation models cannot chat.
+        Ok(models
+            .data
+            .into_iter()
+            .filter(|model| {
+                model
+                    .capabilities
+                    .as_ref()
+                    .is_none_or(|capabilities| capabilities.completion_chat)
+            })
+            .map(|model| ModelInfo {
+                name: model.id,
+                context_limit: model.max_context_length,
+            })
+            .collect())
+    }
+
+    fn context_limit(&self, _model: &str) -> u32 {
+        // Held by every current chat model, the listing tells the exact one.
+        32_000
+    }
 
-    messages.push(json!({
-        "role": "user",
-        "content": text
-    }));
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError> {
+        let mut body = json!({
+            "model": model,
+            "messages": openai::messages(system, transcript, ImageFormat::Url),
+            "stream": stream,
+        });
+        openai::set_params(&mut body, params, "random_seed");
 
-    let body = json!({
-        "model": "mistral-large-latest",
-        "messages": messages,
-    });
+        Ok(Request {
+            url: URL.to_string(),
+            headers: Some(openai::bearer(&credentials.api_key)?),
+            body,
+        })
+    }
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError> {
+        openai::parse_response(response)
+    }
 
-    let mut headers = HeaderMap::new();
-    let token = format!("Bearer {}", api_key);
-    headers.insert(AUTHORIZATION, token.parse()?);
+    fn parse_stream_event(&self, data: &str) -> Result<Option<Delta>, ProviderError> {
+        openai::parse_stream_event(data)
+    }
 
-    let response: Response = fetch(URL, body, Some(headers)).await?;
-    let mut output = String::new();
-    if let Some(choice) = response.choices.first() {
-        output = choice.message.content.clone();
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        let message = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(ErrorBody {
+                message: Value::String(message),
+            }) => message,
+            Ok(ErrorBody { message }) => message.to_string(),
+            Err(_) => body.to_string(),
+        };
+        ProviderError::from_status(status, message)
     }
+}
 
-    Ok(output)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_chat_models() {
+        let models = Mistral
+            .parse_models(json!({"object": "list", "data": [
+                {
+                    "id": "mistral-large-latest",
+                    "capabilities": {"completion_chat": true},
+                    "max_context_length": 131072,
+                },
+                {
+                    "id": "mistral-embed",
+                    "capabilities": {"completion_chat": false},
+                },
+                {"id": "open-mistral-nemo"},
+            ]}))
+            .unwrap();
+        let names = models
+            .iter()
+            .map(|model| model.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["mistral-large-latest", "open-mistral-nemo"]);
+        assert_eq!(models[0].context_limit, Some(131_072));
+    }
 }
@@ -1,3 +1,11 @@
+pub mod anthropic;
+pub mod context;
 pub mod fetch;
 pub mod gemini;
 pub mod mistral;
+pub mod ndjson;
+pub mod ollama;
+pub mod openai;
+pub mod pdf;
+pub mod provider;
+pub mod sse;
@@ -0,0 +1,82 @@
+/// Incremental decoder for newline-delimited JSON bodies, one object per line.
+///
+/// Like `SseDecoder`, bytes are buffered until the line is complete.
+#[derive(Default)]
+pub struct NdjsonDecoder {
+    buffer: Vec<u8>,
+}
+
+impl NdjsonDecoder {
+    /// Feeds a chunk and returns every completed, non-empty line.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            if let Some(line) = parse_line(&line) {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Returns the last line when the body does not end with a newline.
+    pub fn finish(self) -> Option<String> {
+        parse_line(&self.buffer)
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<String> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `body` in chunks of `size` bytes and collects every line.
+    fn decode(body: &[u8], size: usize) -> Vec<String> {
+        let mut decoder = NdjsonDecoder::default();
+        let mut lines = body
+            .chunks(size)
+            .flat_map(|chunk| decoder.push(chunk))
+            .collect::<Vec<_>>();
+        lines.extend(decoder.finish());
+        lines
+    }
+
+    #[test]
+    fn lines_split_mid_line() {
+        let body = b"{\"done\":false}\n{\"done\":true}\n";
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["{\"done\":false}", "{\"done\":true}"]);
+        }
+    }
+
+    #[test]
+    fn characters_split_mid_utf8() {
+        let body = "{\"text\":\"héllo 🌍\"}\n".as_bytes();
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["{\"text\":\"héllo 🌍\"}"]);
+        }
+    }
+
+    #[test]
+    fn crlf_and_blank_lines() {
+        let body = b"{\"a\":1}\r\n\r\n{\"b\":2}\r\n";
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["{\"a\":1}", "{\"b\":2}"]);
+        }
+    }
+
+    #[test]
+    fn trailing_line_without_newline() {
+        let body = b"{\"a\":1}\n{\"b\":2}";
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["{\"a\":1}", "{\"b\":2}"]);
+        }
+    }
+}
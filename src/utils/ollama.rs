@@ -0,0 +1,228 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{
+    fetch::StreamFormat,
+    openai::{self, ImageFormat},
+    provider::{
+        self, Capabilities, Credentials, Delta, ModelInfo, Provider, ProviderError, Request,
+    },
+};
+use crate::types::{Finish, GenerationParams, Turn, Usage};
+
+const DEFAULT_HOST: &str = "http://localhost:11434";
+
+#[derive(Deserialize)]
+struct ReplyMessage {
+    #[serde(default)]
+    content: String,
+}
+
+/// A whole reply, or one line of a streamed one.
+#[derive(Deserialize)]
+struct Response {
+    message: Option<ReplyMessage>,
+    /// Token counts, only on the last line.
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+    /// Why the reply ended, only on the last line.
+    done_reason: Option<String>,
+    /// Set instead of `message` when the model fails mid-stream.
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Model {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Tags {
+    #[serde(default)]
+    models: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
+}
+
+/// Models served by a local Ollama, listed from the host set in the settings.
+pub struct Ollama;
+
+impl Ollama {
+    fn host(credentials: &Credentials) -> &str {
+        match credentials.base_url.trim() {
+            "" => DEFAULT_HOST,
+            host => host.trim_end_matches('/'),
+        }
+    }
+
+    fn delta(response: Response) -> Result<Delta, ProviderError> {
+        if let Some(error) = response.error {
+            return Err(ProviderError::BadRequest(error));
+        }
+        Ok(Delta {
+            text: response
+                .message
+                .map(|message| message.content)
+                .unwrap_or_default(),
+            usage: Usage {
+                input_tokens: response.prompt_eval_count,
+                output_tokens: response.eval_count,
+            },
+            finish: response.done_reason.as_deref().map(Finish::with_reason),
+        })
+    }
+}
+
+impl Provider for Ollama {
+    fn id(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            api_key: false,
+            base_url: true,
+            custom_model: false,
+            streaming: true,
+        }
+    }
+
+    fn models(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn models_request(&self, credentials: &Credentials) -> Option<Request> {
+        Some(Request {
+            url: format!("{}/api/tags", Self::host(credentials)),
+            headers: None,
+            body: Value::Null,
+        })
+    }
+
+    fn parse_models(&self, response: Value) -> Result<Vec<ModelInfo>, ProviderError> {
+        let tags: Tags = serde_json::from_value(response)?;
+        Ok(tags
+            .models
+            .into_iter()
+            .map(|model| ModelInfo::new(model.name))
+            .collect())
+    }
+
+    fn context_limit(&self, _model: &str) -> u32 {
+        // The `num_ctx` Ollama runs models with unless told otherwise.
+        4096
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError> {
+        let mut options = json!({});
+        provider::set(&mut options, "temperature", params.temperature);
+        provider::set(&mut options, "top_p", params.top_p);
+        provider::set(&mut options, "top_k", params.top_k);
+        provider::set(&mut options, "num_predict", params.max_tokens);
+        provider::set(&mut options, "stop", params.stop());
+        provider::set(&mut options, "seed", params.seed);
+
+        Ok(Request {
+            url: format!("{}/api/chat", Self::host(credentials)),
+            headers: None,
+            body: json!({
+                "model": model,
+                "messages": openai::messages(system, transcript, ImageFormat::Base64List),
+                "stream": stream,
+                "options": options,
+            }),
+        })
+    }
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError> {
+        Self::delta(serde_json::from_value(response)?)
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Ndjson
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Option<Delta>, ProviderError> {
+        Ok(Self::delta(serde_json::from_str(data)?)?.non_empty())
+    }
+
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        let message = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(ErrorBody { error }) => error,
+            Err(_) => body.to_string(),
+        };
+        ProviderError::from_status(status, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FinishReason;
+
+    #[test]
+    fn stream_event_text() {
+        let delta = Ollama
+            .parse_stream_event(
+                r#"{"model":"llama3","message":{"role":"assistant","content":"Hi"},"done":false}"#,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.text, "Hi");
+        assert!(delta.usage.is_empty());
+    }
+
+    #[test]
+    fn last_stream_event_usage() {
+        let delta = Ollama
+            .parse_stream_event(
+                r#"{"message":{"role":"assistant","content":""},"done":true,"done_reason":"length","prompt_eval_count":26,"eval_count":290}"#,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            delta.usage,
+            Usage {
+                input_tokens: Some(26),
+                output_tokens: Some(290),
+            }
+        );
+        assert_eq!(delta.finish.unwrap().reason, Some(FinishReason::MaxTokens));
+    }
+
+    #[test]
+    fn stream_event_error() {
+        let error = Ollama
+            .parse_stream_event(r#"{"error":"model runner has unexpectedly stopped"}"#)
+            .unwrap_err();
+        assert!(matches!(error, ProviderError::BadRequest(message) if message.contains("stopped")));
+    }
+
+    #[test]
+    fn installed_models() {
+        let models = Ollama
+            .parse_models(json!({"models": [{"name": "llama3:8b"}, {"name": "qwen2.5:7b"}]}))
+            .unwrap();
+        let names = models
+            .iter()
+            .map(|model| model.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["llama3:8b", "qwen2.5:7b"]);
+    }
+}
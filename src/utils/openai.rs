@@ -0,0 +1,339 @@
+use reqwest::{
+    header::{HeaderMap, AUTHORIZATION},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::provider::{self, Capabilities, Credentials, Delta, Provider, ProviderError, Request};
+use crate::types::{Attachment, Finish, GenerationParams, Role, Turn, Usage};
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+#[derive(Deserialize)]
+struct ReplyMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ReplyMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenUsage {
+    prompt_tokens: Option<u32>,
+    completion_tokens: Option<u32>,
+}
+
+impl From<TokenUsage> for Usage {
+    fn from(usage: TokenUsage) -> Self {
+        Usage {
+            input_tokens: usage.prompt_tokens,
+            output_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    choices: Vec<Choice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: ChunkDelta,
+    /// Only on the last chunk with choices.
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    /// Only on the last chunk.
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+// The `/chat/completions` schema, shared with the providers that copied it.
+
+/// How the implementations of the schema take the images of a message.
+pub(super) enum ImageFormat {
+    /// `image_url` blocks holding a `{"url": ...}` object.
+    UrlObject,
+    /// `image_url` blocks holding the data URL itself.
+    Url,
+    /// An `images` list of base64 strings beside the text.
+    Base64List,
+}
+
+/// The `system` instructions, if any, lead the conversation as their own message.
+pub(super) fn messages(system: &str, transcript: &[Turn], images: ImageFormat) -> Vec<Value> {
+    let system = (!system.trim().is_empty()).then(|| {
+        json!({
+            "role": "system",
+            "content": system
+        })
+    });
+    system
+        .into_iter()
+        .chain(transcript.iter().map(|turn| {
+            let role = match turn.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            let mut message = json!({
+                "role": role,
+                "content": turn.content
+            });
+            if turn.images().next().is_none() {
+                return message;
+            }
+            let block = |url: Value| json!({ "type": "image_url", "image_url": url });
+            match images {
+                ImageFormat::UrlObject => {
+                    message["content"] =
+                        content_blocks(turn, |image| block(json!({ "url": image.data_url() })))
+                }
+                ImageFormat::Url => {
+                    message["content"] =
+                        content_blocks(turn, |image| block(json!(image.data_url())))
+                }
+                ImageFormat::Base64List => {
+                    message["images"] = turn.images().map(|image| json!(image.data)).collect()
+                }
+            }
+            message
+        }))
+        .collect()
+}
+
+fn content_blocks(turn: &Turn, image: impl Fn(&Attachment) -> Value) -> Value {
+    let text = (!turn.content.is_empty()).then(|| json!({ "type": "text", "text": turn.content }));
+    text.into_iter().chain(turn.images().map(image)).collect()
+}
+
+pub(super) fn bearer(api_key: &str) -> Result<HeaderMap, ProviderError> {
+    let mut headers = HeaderMap::new();
+    let token = format!("Bearer {}", api_key)
+        .parse()
+        .map_err(|_| ProviderError::Auth("the API key is not a valid header value".into()))?;
+    headers.insert(AUTHORIZATION, token);
+    Ok(headers)
+}
+
+/// Adds the sampling settings, `seed_key` differs between implementations.
+pub(super) fn set_params(body: &mut Value, params: &GenerationParams, seed_key: &str) {
+    provider::set(body, "temperature", params.temperature);
+    provider::set(body, "top_p", params.top_p);
+    provider::set(body, "max_tokens", params.max_tokens);
+    provider::set(body, "stop", params.stop());
+    provider::set(body, seed_key, params.seed);
+}
+
+pub(super) fn parse_response(response: Value) -> Result<Delta, ProviderError> {
+    let response: Response = serde_json::from_value(response)?;
+    let choice = response.choices.into_iter().next();
+    Ok(Delta {
+        finish: choice
+            .as_ref()
+            .and_then(|choice| choice.finish_reason.as_deref())
+            .map(Finish::with_reason),
+        text: choice
+            .and_then(|choice| choice.message.content)
+            .unwrap_or_default(),
+        usage: response.usage.map(Usage::from).unwrap_or_default(),
+    })
+}
+
+pub(super) fn parse_stream_event(data: &str) -> Result<Option<Delta>, ProviderError> {
+    if data == "[DONE]" {
+        return Ok(None);
+    }
+    let chunk: Chunk = serde_json::from_str(data)?;
+    let choice = chunk.choices.into_iter().next();
+    let delta = Delta {
+        finish: choice
+            .as_ref()
+            .and_then(|choice| choice.finish_reason.as_deref())
+            .map(Finish::with_reason),
+        text: choice
+            .and_then(|choice| choice.delta.content)
+            .unwrap_or_default(),
+        usage: chunk.usage.map(Usage::from).unwrap_or_default(),
+    };
+    Ok(delta.non_empty())
+}
+
+/// Any server implementing the OpenAI chat completions API, e.g. OpenAI,
+/// OpenRouter, Groq, vLLM or LM Studio.
+pub struct OpenAI;
+
+impl Provider for OpenAI {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    fn name(&self) -> &'static str {
+        "OpenAI compatible"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            api_key: true,
+            base_url: true,
+            custom_model: true,
+            streaming: true,
+        }
+    }
+
+    fn models(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn context_limit(&self, _model: &str) -> u32 {
+        // What current OpenAI models hold, local servers may hold less.
+        128_000
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError> {
+        let base_url = match credentials.base_url.trim() {
+            "" => DEFAULT_BASE_URL,
+            base_url => base_url.trim_end_matches('/'),
+        };
+        // Local servers usually run without a key.
+        let headers = match credentials.api_key.as_str() {
+            "" => None,
+            api_key => Some(bearer(api_key)?),
+        };
+
+        let mut body = json!({
+            "model": model,
+            "messages": messages(system, transcript, ImageFormat::UrlObject),
+            "stream": stream,
+        });
+        set_params(&mut body, params, "seed");
+        if stream {
+            // Without it the usage is left out of streamed replies.
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        Ok(Request {
+            url: format!("{base_url}/chat/completions"),
+            headers,
+            body,
+        })
+    }
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError> {
+        parse_response(response)
+    }
+
+    fn parse_stream_event(&self, data: &str) -> Result<Option<Delta>, ProviderError> {
+        parse_stream_event(data)
+    }
+
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        let message = match serde_json::from_str::<ErrorBody>(body) {
+            Ok(ErrorBody { error }) => error.message,
+            Err(_) => body.to_string(),
+        };
+        ProviderError::from_status(status, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FinishReason;
+
+    #[test]
+    fn stream_event_text() {
+        let delta = parse_stream_event(
+            r#"{"id":"1","choices":[{"index":0,"delta":{"role":"assistant","content":"Hel"}}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(delta.text, "Hel");
+        assert!(delta.usage.is_empty());
+    }
+
+    #[test]
+    fn stream_event_usage_without_choices() {
+        let delta = parse_stream_event(
+            r#"{"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":34}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(delta.text, "");
+        assert_eq!(
+            delta.usage,
+            Usage {
+                input_tokens: Some(12),
+                output_tokens: Some(34),
+            }
+        );
+    }
+
+    #[test]
+    fn stream_event_empty_or_done() {
+        assert!(parse_stream_event(r#"{"choices":[{"delta":{}}]}"#)
+            .unwrap()
+            .is_none());
+        assert!(parse_stream_event("[DONE]").unwrap().is_none());
+    }
+
+    #[test]
+    fn stream_event_cut_by_max_tokens() {
+        let delta = parse_stream_event(r#"{"choices":[{"delta":{},"finish_reason":"length"}]}"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delta.finish.unwrap().reason, Some(FinishReason::MaxTokens));
+    }
+
+    #[test]
+    fn stream_event_invalid_json() {
+        assert!(parse_stream_event("{\"choices\":").is_err());
+    }
+
+    #[test]
+    fn whole_response() {
+        let delta = parse_response(json!({
+            "choices": [{
+                "message": {"role": "assistant", "content": "Hello"},
+                "finish_reason": "stop",
+            }],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 1},
+        }))
+        .unwrap();
+        assert_eq!(delta.text, "Hello");
+        assert_eq!(delta.usage.output_tokens, Some(1));
+        assert_eq!(delta.finish.unwrap().reason, Some(FinishReason::Stop));
+    }
+}
@@ -0,0 +1,57 @@
+use std::panic::{self, AssertUnwindSafe};
+
+/// Share of the characters that can be unprintable, e.g. from fonts with a
+/// custom encoding, before the text is taken as garbled.
+const MAX_UNPRINTABLE: f64 = 0.1;
+
+/// Text of the pages of a PDF, or why it cannot be sent.
+pub fn extract_text(pdf: &[u8]) -> Result<String, String> {
+    // The parser panics on some malformed files.
+    let text = panic::catch_unwind(AssertUnwindSafe(|| pdf_extract::extract_text_from_mem(pdf)))
+        .map_err(|_| "the PDF could not be read".to_string())?
+        .map_err(|err| format!("the PDF could not be read ({err})"))?;
+
+    let (characters, unprintable) =
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .fold((0, 0), |(characters, unprintable), c| {
+                let printable = !c.is_control() && c != char::REPLACEMENT_CHARACTER;
+                (characters + 1, unprintable + usize::from(!printable))
+            });
+    if characters == 0 {
+        return Err("no text found, the PDF may be scanned".to_string());
+    }
+    if unprintable as f64 > characters as f64 * MAX_UNPRINTABLE {
+        return Err("the text could not be decoded, its fonts may be embedded".to_string());
+    }
+    Ok(text.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_of_the_pages() {
+        let text = extract_text(include_bytes!("../../tests/fixtures/hello.pdf")).unwrap();
+        assert!(text.contains("Hello from a PDF"));
+        assert!(text.contains("Second line"));
+    }
+
+    #[test]
+    fn rejects_a_pdf_without_text() {
+        let error = extract_text(include_bytes!("../../tests/fixtures/blank.pdf")).unwrap_err();
+        assert!(error.contains("no text"));
+    }
+
+    #[test]
+    fn rejects_unprintable_text() {
+        let error = extract_text(include_bytes!("../../tests/fixtures/garbled.pdf")).unwrap_err();
+        assert!(error.contains("could not be decoded"));
+    }
+
+    #[test]
+    fn rejects_a_file_that_is_not_a_pdf() {
+        assert!(extract_text(b"%PDF-1.4\nnot really").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{
+    anthropic::Anthropic, fetch::StreamFormat, gemini::Gemini, mistral::Mistral, ollama::Ollama,
+    openai::OpenAI,
+};
+use crate::types::{Finish, GenerationParams, Turn, Usage};
+
+/// Features a backend supports, the UI and services adapt to them.
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    /// Whether requests need the API key saved in the settings screen.
+    pub api_key: bool,
+    /// Whether the server address is set in the settings screen.
+    pub base_url: bool,
+    /// Whether the model name is typed in the settings screen instead of listed.
+    pub custom_model: bool,
+    /// Whether replies can be streamed as server-sent events.
+    pub streaming: bool,
+}
+
+/// Why a provider could not answer, shown as an error bubble instead of a reply.
+#[derive(Debug, Clone)]
+pub enum ProviderError {
+    Auth(String),
+    Quota(String),
+    Safety(String),
+    BadRequest(String),
+    Network(String),
+    Decode(String),
+}
+
+impl ProviderError {
+    /// Classifies an HTTP error from its status, `message` comes from the body.
+    pub fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ProviderError::Auth(message),
+            StatusCode::TOO_MANY_REQUESTS => ProviderError::Quota(message),
+            status if status.is_client_error() => ProviderError::BadRequest(message),
+            status => ProviderError::Network(format!("{status}: {message}")),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::Auth(msg) => {
+                write!(
+                    f,
+                    "Authentication failed, check your API key in the settings: {msg}"
+                )
+            }
+            ProviderError::Quota(msg) => write!(f, "Quota or rate limit exceeded: {msg}"),
+            ProviderError::Safety(msg) => write!(f, "Blocked by the safety filters: {msg}"),
+            ProviderError::BadRequest(msg) => write!(f, "The request was rejected: {msg}"),
+            ProviderError::Network(msg) => write!(f, "Network error: {msg}"),
+            ProviderError::Decode(msg) => write!(f, "Unexpected response: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<serde_json::Error> for ProviderError {
+    fn from(err: serde_json::Error) -> Self {
+        ProviderError::Decode(err.to_string())
+    }
+}
+
+/// Text and token counts of a reply, or what one event of a streamed reply adds.
+#[derive(Clone, Debug, Default)]
+pub struct Delta {
+    pub text: String,
+    pub usage: Usage,
+    /// Set by the providers that report it, on the last event of a streamed reply.
+    pub finish: Option<Finish>,
+}
+
+impl Delta {
+    pub fn text(text: String) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.usage.is_empty() && self.finish.is_none()
+    }
+
+    /// `None` when the event carried nothing, like a keep-alive.
+    pub fn non_empty(self) -> Option<Self> {
+        (!self.is_empty()).then_some(self)
+    }
+}
+
+/// What the user saved for a provider in the settings screen.
+#[derive(Clone, Debug, Default)]
+pub struct Credentials {
+    pub api_key: String,
+    pub base_url: String,
+    /// Threshold of each safety category set, by category.
+    pub safety_settings: Vec<(String, String)>,
+}
+
+// The API key is saved under the bare provider id, like older versions did.
+
+pub fn base_url_key(provider: &dyn Provider) -> String {
+    format!("{}.base_url", provider.id())
+}
+
+pub fn model_key(provider: &dyn Provider) -> String {
+    format!("{}.model", provider.id())
+}
+
+pub fn safety_key(provider: &dyn Provider, category: &str) -> String {
+    format!("{}.safety.{category}", provider.id())
+}
+
+/// Adds `value` to a JSON object when it is set, for optional request fields.
+pub fn set<T: Serialize>(object: &mut Value, key: &str, value: Option<T>) {
+    if let (Some(value), Some(object)) = (value, object.as_object_mut()) {
+        object.insert(key.to_string(), json!(value));
+    }
+}
+
+/// A model listed by a provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub name: String,
+    /// Tokens its context window holds, when the listing tells.
+    pub context_limit: Option<u32>,
+}
+
+impl ModelInfo {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            context_limit: None,
+        }
+    }
+}
+
+/// An HTTP request ready to be sent by `fetch`.
+pub struct Request {
+    pub url: String,
+    pub headers: Option<HeaderMap>,
+    /// Unused by the GET requests listing models.
+    pub body: Value,
+}
+
+/// Small enough for most models still served.
+const DEFAULT_CONTEXT_LIMIT: u32 = 8192;
+
+pub trait Provider: Sync {
+    /// Stable identifier, used to persist the choice and as the settings form key.
+    fn id(&self) -> &'static str;
+
+    /// Name shown in the settings screen.
+    fn name(&self) -> &'static str;
+
+    fn capabilities(&self) -> Capabilities;
+
+    fn models(&self) -> &'static [&'static str];
+
+    /// GET request listing the models available to the user, for providers
+    /// whose models cannot be known in advance.
+    fn models_request(&self, _credentials: &Credentials) -> Option<Request> {
+        None
+    }
+
+    fn parse_models(&self, _response: Value) -> Result<Vec<ModelInfo>, ProviderError> {
+        Ok(Vec::new())
+    }
+
+    /// Harm categories whose blocking threshold can be set, with their labels.
+    fn safety_categories(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// Thresholds a safety category can be set to.
+    fn safety_thresholds(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Tokens the context window of `model` holds when its listing does not tell.
+    fn context_limit(&self, _model: &str) -> u32 {
+        DEFAULT_CONTEXT_LIMIT
+    }
+
+    fn build_request(
+        &self,
+        model: &str,
+        system: &str,
+        transcript: &[Turn],
+        credentials: &Credentials,
+        params: &GenerationParams,
+        stream: bool,
+    ) -> Result<Request, ProviderError>;
+
+    fn parse_response(&self, response: Value) -> Result<Delta, ProviderError>;
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
+
+    /// Parses one event of the streamed reply.
+    fn parse_stream_event(&self, _data: &str) -> Result<Option<Delta>, ProviderError> {
+        Ok(None)
+    }
+
+    /// Turns the body of a failed HTTP response into an error.
+    fn parse_error(&self, status: StatusCode, body: &str) -> ProviderError;
+}
+
+/// Every available backend, a new one only needs to be added here.
+static PROVIDERS: &[&dyn Provider] = &[&Gemini, &Mistral, &Anthropic, &OpenAI, &Ollama];
+
+pub fn providers() -> &'static [&'static dyn Provider] {
+    PROVIDERS
+}
+
+pub fn get(id: &str) -> Option<&'static dyn Provider> {
+    PROVIDERS
+        .iter()
+        .copied()
+        .find(|provider| provider.id() == id)
+}
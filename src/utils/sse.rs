@@ -0,0 +1,106 @@
+/// Incremental decoder for `text/event-stream` bodies.
+///
+/// Network chunks can end anywhere, even in the middle of a UTF-8 character,
+/// so bytes are buffered until a blank line terminates the event.
+#[derive(Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    /// Feeds a chunk and returns the `data` payload of every completed event.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some((end, separator)) = find_event_end(&self.buffer) {
+            let event = self.buffer.drain(..end + separator).collect::<Vec<_>>();
+            if let Some(data) = parse_event(&String::from_utf8_lossy(&event[..end])) {
+                events.push(data);
+            }
+        }
+        events
+    }
+
+    /// Returns the last event when the stream ends without a trailing blank line.
+    pub fn finish(self) -> Option<String> {
+        parse_event(&String::from_utf8_lossy(&self.buffer))
+    }
+}
+
+fn find_event_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|i| (i, 2));
+    let crlf = buffer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| (i, 4));
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(if a.0 < b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+fn parse_event(event: &str) -> Option<String> {
+    let data = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect::<Vec<_>>();
+    (!data.is_empty()).then(|| data.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `body` in chunks of `size` bytes and collects every event.
+    fn decode(body: &[u8], size: usize) -> Vec<String> {
+        let mut decoder = SseDecoder::default();
+        let mut events = body
+            .chunks(size)
+            .flat_map(|chunk| decoder.push(chunk))
+            .collect::<Vec<_>>();
+        events.extend(decoder.finish());
+        events
+    }
+
+    #[test]
+    fn events_split_mid_line() {
+        let body = b"data: {\"text\":\"hello\"}\n\ndata: {\"text\":\"world\"}\n\n";
+        for size in 1..body.len() {
+            assert_eq!(
+                decode(body, size),
+                ["{\"text\":\"hello\"}", "{\"text\":\"world\"}"]
+            );
+        }
+    }
+
+    #[test]
+    fn characters_split_mid_utf8() {
+        let body = "data: héllo 🌍\n\n".as_bytes();
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["héllo 🌍"]);
+        }
+    }
+
+    #[test]
+    fn crlf_separators() {
+        let body = b"event: delta\r\ndata: one\r\n\r\ndata: two\r\n\r\n";
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["one", "two"]);
+        }
+    }
+
+    #[test]
+    fn trailing_event_without_blank_line() {
+        let body = b"data: one\n\ndata: two";
+        for size in 1..body.len() {
+            assert_eq!(decode(body, size), ["one", "two"]);
+        }
+    }
+
+    #[test]
+    fn multiline_data_and_comments() {
+        assert_eq!(decode(b": keep-alive\n\ndata: a\ndata: b\n\n", 3), ["a\nb"]);
+    }
+}